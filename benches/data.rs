@@ -0,0 +1,85 @@
+/* Compares the rope backed Data against the Vec<String> buffer it replaced,
+ * on a large generated Haskell file. Run with `cargo bench`. */
+
+use std::time::{ Duration, Instant };
+
+use editor::mods::data::Data;
+
+const ROWS: usize = 200_000;
+const EDITS: usize = 2_000;
+
+// The previous representation of the buffer, kept here as a baseline
+struct VecData {
+    info: Vec<String>,
+}
+
+impl VecData {
+    fn from_text(source: &str) -> VecData {
+        VecData { info: source.lines().map(|line| line.to_string()).collect() }
+    }
+    fn insert_char(&mut self, row: usize, col: usize, c: char) {
+        self.info[row].insert(col, c);
+    }
+    fn break_line(&mut self, row: usize, col: usize) {
+        let tail = self.info[row][col ..].to_string();
+        self.info[row].truncate(col);
+        self.info.insert(row + 1, tail);
+    }
+    fn join_line(&mut self, row: usize) {
+        let next = self.info.remove(row + 1);
+        self.info[row].push_str(&next);
+    }
+    fn contents(&self) -> String {
+        self.info.join("\n") + "\n"
+    }
+}
+
+fn generate_source() -> String {
+    let mut source = String::new();
+    for i in 0 .. ROWS {
+        source.push_str(&format!("step{} :: Int -> Maybe Int\nstep{} x = Just (x + {})\n", i, i, i));
+    }
+    source
+}
+
+fn time<F: FnMut()>(mut f: F) -> Duration {
+    let start = Instant::now();
+    f();
+    start.elapsed()
+}
+
+fn report(name: &str, vec: Duration, rope: Duration) {
+    println!("{:<24} Vec<String>: {:>10.2?}   rope: {:>10.2?}   ({:.2}x)",
+             name,
+             vec,
+             rope,
+             vec.as_secs_f64() / rope.as_secs_f64());
+}
+
+fn main() {
+    let source = generate_source();
+    println!("buffer of {} rows, {} bytes", 2 * ROWS, source.len());
+
+    let mut vec = VecData::from_text(&source);
+    let mut rope = Data::from_text(&source);
+    report("load",
+           time(|| { VecData::from_text(&source); }),
+           time(|| { Data::from_text(&source); }));
+
+    // in the middle of the file, where the Vec has the most rows to move
+    let row = ROWS;
+    report("type characters",
+           time(|| for col in 0 .. EDITS { vec.insert_char(row, col, 'x') }),
+           time(|| for col in 0 .. EDITS { rope.insert_char(row, col, 'x') }));
+    report("break lines",
+           time(|| for _ in 0 .. EDITS { vec.break_line(row, 4) }),
+           time(|| for _ in 0 .. EDITS { rope.insert_text(row, 4, "\n"); }));
+    report("join lines",
+           time(|| for _ in 0 .. EDITS { vec.join_line(row) }),
+           time(|| for _ in 0 .. EDITS { rope.remove_text(row, 4, row + 1, 0); }));
+    report("save",
+           time(|| { vec.contents(); }),
+           time(|| { rope.to_string(); }));
+
+    assert_eq!(vec.contents(), rope.to_string());
+}
@@ -1,22 +1,59 @@
 use std::env;
+use std::fs;
+use std::panic::{ self, AssertUnwindSafe };
+use std::process;
+use std::sync::Mutex;
 
-mod mods;
+use editor::mods::config::Config;
+use editor::mods::state::State;
+use editor::mods::interface;
 
-use mods::config::Config;
-use mods::state::State;
-use mods::interface;
+// Kept by the panic hook, to be shown once the terminal is restored
+static PANIC_MESSAGE: Mutex<Option<String>> = Mutex::new(None);
 
-fn main() -> Result<(), &'static str> {
+fn main() {
     let args: Vec<String> = env::args().collect();
 
-    let (width, height) = termion::terminal_size().unwrap();
-
-    let config = Config::new(&args, height, width)?;
-
-    let mut state = State::create(config);
-
-    // termion::async_stdin();
-
-    interface::run(&mut state);
-    Ok(())
+    // when the size can't be known, the usual one is a good guess
+    let (width, height) = termion::terminal_size().unwrap_or((80, 24));
+
+    let config = match Config::new(&args, height, width) {
+        Ok(config) => config,
+        Err(msg)   => { eprintln!("{}", msg); process::exit(1); }
+    };
+
+    /* Printing the panic right away would be lost in the alternate screen,
+     * so it waits until the terminal is restored, when State is dropped. */
+    panic::set_hook(Box::new(|info| {
+        if let Ok(mut message) = PANIC_MESSAGE.lock() {
+            *message = Some(info.to_string());
+        }
+    }));
+
+    let mut state: Option<State> = None;
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        State::create(config).map(|created| interface::run(state.insert(created)))
+    }));
+
+    if let Ok(Err(msg)) = result {
+        eprintln!("{}", msg);
+        process::exit(1);
+    }
+
+    if result.is_err() {
+        let written = panic::catch_unwind(AssertUnwindSafe(|| {
+            state.as_mut().map_or(Vec::new(), |state| state.rescue())
+        })).unwrap_or_default();
+        drop(state);
+        let message = PANIC_MESSAGE.lock().ok().and_then(|message| message.clone()).unwrap_or_default();
+        eprintln!("The editor crashed: {}", message);
+        let log = env::temp_dir().join(format!("functional-editor-crash-{}.log", process::id()));
+        if fs::write(&log, &message).is_ok() {
+            eprintln!("This message was also written to {}", log.display());
+        }
+        for path in written {
+            eprintln!("Unsaved changes written to {}", path);
+        }
+        process::exit(101);
+    }
 }
@@ -1,8 +1,20 @@
+pub mod buffer;
+pub mod clipboard;
 pub mod config;
 pub mod data;
+pub mod grid;
+pub mod history;
 pub mod state;
+#[cfg(feature = "tree-sitter")]
+pub mod syntax;
 pub mod term;
+pub mod theme;
+pub mod window;
+pub mod input;
 pub mod interface;
+pub mod keymap;
 pub mod lib;
+pub mod modal;
 pub mod languages;
+pub mod lexer;
 
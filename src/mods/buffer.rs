@@ -0,0 +1,392 @@
+use std::cell::RefCell;
+use std::fs::{ self, File, OpenOptions };
+use std::io::{ self, BufWriter, Write };
+use std::path::{ Path, PathBuf };
+use std::process;
+
+use super::config::{ Config, Tabs };
+use super::data::Data;
+use super::history::{ Change, History };
+use super::languages::{ self, ColorsConfig };
+use super::lexer::{ self, Context, Token };
+use super::lib;
+#[cfg(feature = "tree-sitter")]
+use super::syntax::SyntaxTree;
+use super::window::View;
+
+// First line of the swap files, so that the ones of other editors are left alone
+const SWAP_HEADER: &str = "functional-editor swap file\n";
+
+/* A file open in the editor, with its own undo history. The view keeps the
+ * cursor and scroll offsets of the buffer while another one is displayed.
+ * The lexer context at the start of each row is kept up to the first row
+ * changed since, so that only the rows after an edit are tokenized again. */
+pub struct Buffer {
+    pub data     : Data,
+    pub history  : History,
+    pub view     : View,
+    file_name    : String,
+    colors_cfg   : ColorsConfig,
+    contexts     : RefCell<Vec<Context>>,
+    #[cfg(feature = "tree-sitter")]
+    syntax       : RefCell<Option<SyntaxTree>>,
+    swap_version : usize,
+}
+
+impl Buffer {
+    pub fn new(config: &Config) -> Buffer {
+        // we need at least one row, otherwise it won't be possible to write
+        Buffer::with_data("", Data::from_vec(vec![String::new()]), config)
+    }
+
+    /* Reads the file if it exists, otherwise the buffer starts empty. Fails
+     * on what can't be read as text, like a directory or non-UTF-8 bytes. */
+    pub fn open(file_name: &str, config: &Config) -> io::Result<Buffer> {
+        if !Path::new(file_name).exists() {
+            return Ok(Buffer::with_data(file_name, Data::from_vec(vec![String::new()]), config));
+        }
+        let text = fs::read_to_string(file_name)?;
+        Ok(Buffer::with_data(file_name, Data::from_text(&text), config))
+    }
+
+    fn with_data(file_name: &str, data: Data, config: &Config) -> Buffer {
+        let colors_cfg = config.colors_for(file_name, &data);
+        Buffer {
+            data,
+            history: History::new(),
+            view: Default::default(),
+            file_name: file_name.to_string(),
+            colors_cfg,
+            contexts: RefCell::new(vec![Context::Code]),
+            #[cfg(feature = "tree-sitter")]
+            syntax: RefCell::new(SyntaxTree::new(colors_cfg.language)),
+            swap_version: 0,
+        }
+    }
+
+    pub fn file_name(&self) -> &String {
+        &self.file_name
+    }
+
+    pub fn set_file_name(&mut self, name: &str, config: &Config) {
+        self.file_name = name.to_string();
+        self.set_colors(config.colors_for(name, &self.data));
+    }
+
+    /* Highlights the buffer as a language of languages::LANGUAGE_NAMES,
+     * whatever its file name says. Returns false for unknown languages. */
+    pub fn set_language(&mut self, name: &str) -> bool {
+        match languages::by_name(name) {
+            Some(colors_cfg) => { self.set_colors(colors_cfg); true }
+            None             => false,
+        }
+    }
+
+    fn set_colors(&mut self, colors_cfg: ColorsConfig) {
+        self.colors_cfg = colors_cfg;
+        self.contexts = RefCell::new(vec![Context::Code]);
+        #[cfg(feature = "tree-sitter")]
+        {
+            self.syntax = RefCell::new(SyntaxTree::new(colors_cfg.language));
+            self.data.forget_edits();
+        }
+    }
+
+    /* Writes the text to a temporary file next to the original one and then
+     * renames it over the original, so that a failed save never leaves it
+     * half written. The permissions of the original are kept, and with
+     * backup it is first copied to the same name followed by '~'. The
+     * rename would replace a file that can't be written as well, so those
+     * are refused first. */
+    pub fn save(&self, backup: bool) -> io::Result<()> {
+        // saving through a symbolic link replaces the file it points to
+        let path = fs::canonicalize(&self.file_name)
+                       .unwrap_or_else(|_| PathBuf::from(&self.file_name));
+        let permissions = fs::metadata(&path).ok().map(|metadata| metadata.permissions());
+        if let Some(permissions) = &permissions {
+            if permissions.readonly() {
+                return Err(io::Error::new(io::ErrorKind::PermissionDenied, "the file is read-only"));
+            }
+            OpenOptions::new().write(true).open(&path)?;
+        }
+        if backup && permissions.is_some() {
+            let mut backup_name = path.clone().into_os_string();
+            backup_name.push("~");
+            fs::copy(&path, backup_name)?;
+        }
+
+        let file_name = path.file_name().ok_or(io::ErrorKind::InvalidInput)?.to_string_lossy();
+        let temp_path = path.with_file_name(format!(".{}.{}.tmp", file_name, process::id()));
+        let result = self.write_to(&temp_path).and_then(|()| {
+            if let Some(permissions) = permissions {
+                fs::set_permissions(&temp_path, permissions)?;
+            }
+            fs::rename(&temp_path, &path)
+        });
+        if result.is_err() {
+            let _ = fs::remove_file(&temp_path);
+        }
+        result
+    }
+
+    fn write_to(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        write!(writer, "{}", self.data)?;
+        writer.into_inner().map_err(|err| err.into_error())?.sync_all()
+    }
+
+    /* Where the unsaved text is kept in case the editor dies: .name.fe-swp
+     * next to the file, not to be mistaken for the .name.swp of vim. */
+    pub fn swap_path(&self) -> Option<PathBuf> {
+        let path = Path::new(&self.file_name);
+        let name = path.file_name()?.to_string_lossy();
+        Some(path.with_file_name(format!(".{}.fe-swp", name)))
+    }
+
+    // Writes the text to a swap file, unless a file this editor didn't write is there
+    pub fn save_swap(&self, path: &Path) -> io::Result<()> {
+        if path.exists() && read_swap(path).is_none() {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists,
+                                      format!("{} isn't a swap file", path.display())));
+        }
+        fs::write(path, format!("{}{}", SWAP_HEADER, self.data))
+    }
+
+    /* Writes the text to the swap file if it changed since the last time, or
+     * removes the swap file if there is nothing left to save. */
+    pub fn write_swap(&mut self) -> io::Result<()> {
+        let version = self.history.version();
+        if version == self.swap_version {
+            return Ok(());
+        }
+        // also on errors, so that they are reported only once
+        self.swap_version = version;
+        match self.swap_path() {
+            Some(path) if self.is_modified() => self.save_swap(&path),
+            _                                => { self.remove_swap(); Ok(()) }
+        }
+    }
+
+    pub fn remove_swap(&mut self) {
+        if let Some(path) = self.swap_path().filter(|path| read_swap(path).is_some()) {
+            let _ = fs::remove_file(path);
+        }
+        self.swap_version = self.history.version();
+    }
+
+    // The text of a swap file written after the file was last changed
+    pub fn newer_swap(&self) -> Option<String> {
+        let swap_path = self.swap_path()?;
+        let swap_time = fs::metadata(&swap_path).and_then(|metadata| metadata.modified()).ok()?;
+        let file_time = fs::metadata(&self.file_name).and_then(|metadata| metadata.modified());
+        if file_time.is_ok_and(|time| time > swap_time) {
+            return None;
+        }
+        read_swap(&swap_path)
+    }
+
+    // Replaces the text by the one of a swap file, as an edit that can be undone
+    pub fn recover(&mut self, text: &str) {
+        let last = self.data.len() - 1;
+        let old = self.data.remove_text(0, 0, last, self.data.row_length(last));
+        let new = text.strip_suffix('\n').unwrap_or(text);
+        self.data.insert_text(0, 0, new);
+        let changes = vec![
+            Change::Remove { row: 0, col: 0, text: old },
+            Change::Insert { row: 0, col: 0, text: new.to_string() },
+        ];
+        self.history.record_group(changes, (0, 0), (0, 0));
+    }
+
+    pub fn is_modified(&self) -> bool {
+        self.history.is_modified()
+    }
+
+    // Name shown to the user, also for buffers that were never saved
+    pub fn display_name(&self) -> String {
+        if self.file_name.is_empty() {
+            "[No Name]".to_string()
+        } else {
+            self.file_name.clone()
+        }
+    }
+
+    /* The name of the style of each byte of the row in the theme, from the
+     * tree-sitter grammar of the language if there is one, and from the
+     * lexer otherwise. */
+    pub fn row_styles(&self, row: usize) -> Vec<&'static str> {
+        #[cfg(feature = "tree-sitter")]
+        {
+            if let Some(syntax) = self.syntax.borrow_mut().as_mut() {
+                if let Some(styles) = syntax.row_styles(&self.data, row) {
+                    return styles;
+                }
+            }
+        }
+        let text = self.data.get_row(row);
+        let (tokens, _) = lexer::tokenize(&text, &self.colors_cfg, self.context_at(row));
+        let mut styles = Vec::with_capacity(text.len());
+        for (token, end) in tokens {
+            let style = self.colors_cfg.style_of(token, &text[styles.len() .. end]);
+            styles.resize(end, style);
+        }
+        styles
+    }
+
+    // The language of the file, whose styles can be changed in the config file
+    pub fn language(&self) -> &'static str {
+        self.colors_cfg.language
+    }
+
+    pub fn tabs(&self, config: &Config) -> Tabs {
+        config.tabs(&self.colors_cfg)
+    }
+
+    /* The indentation of a new row made by breaking the row at col: the one
+     * of the row (with its bird track in literate Haskell), and one more
+     * level when the code before col opens a block. Comments after the code
+     * don't count. */
+    pub fn indent_after(&self, row: usize, col: usize, tabs: Tabs) -> String {
+        let text = self.data.get_row(row);
+        let before = &text[.. lib::byte_index(&text, col)];
+        let margin = if self.colors_cfg.bird_tracks && before.starts_with('>') { 1 } else { 0 };
+        let blank = before[margin ..].len() - before[margin ..].trim_start().len();
+        let mut indent = before[.. margin + blank].to_string();
+
+        let (tokens, _) = lexer::tokenize(before, &self.colors_cfg, self.context_at(row));
+        let code_end = tokens.iter()
+                             .rev()
+                             .find(|&&(token, _)| token != Token::Comment)
+                             .map_or(0, |&(_, end)| end);
+        if (self.colors_cfg.indent.opens_block)(before[.. code_end].trim_end()) {
+            if tabs.hard {
+                indent.push('\t');
+            } else {
+                indent.push_str(&" ".repeat(tabs.width as usize));
+            }
+        }
+        indent
+    }
+
+    // Whether typing c first in a row takes a level of indentation off it
+    pub fn dedents(&self, c: char) -> bool {
+        self.colors_cfg.indent.closing.contains(c)
+    }
+
+    /* Where a closing char typed at col goes, when there is only indentation
+     * before it: back to the previous tab stop, or over the last tab. */
+    pub fn dedent_start(&self, row: usize, col: usize, tabs: Tabs) -> Option<usize> {
+        let text = self.data.get_row(row);
+        let before = &text[.. lib::byte_index(&text, col)];
+        if before.is_empty() || !before.trim_start().is_empty() {
+            return None;
+        }
+        let width =
+            if before.ends_with('\t') {
+                1
+            } else {
+                let cell = lib::display_col(&text, col, tabs.width as usize);
+                let spaces = before.len() - before.trim_end_matches(' ').len();
+                std::cmp::min((cell + tabs.width as usize - 1) % tabs.width as usize + 1, spaces)
+            };
+        Some(col - width)
+    }
+
+    // Chars that are part of words in the language, besides letters, digits and '_'
+    pub fn word_chars(&self) -> &'static str {
+        self.colors_cfg.word_chars
+    }
+
+    fn context_at(&self, row: usize) -> Context {
+        let mut contexts = self.contexts.borrow_mut();
+        contexts.truncate(self.data.take_changes().saturating_add(1));
+        while contexts.len() <= row {
+            let last = contexts.len() - 1;
+            let text = self.data.get_row(last);
+            let (_, context) = lexer::tokenize(&text, &self.colors_cfg, contexts[last]);
+            contexts.push(context);
+        }
+        contexts[row]
+    }
+}
+
+// The text of a swap file, if this editor wrote it
+fn read_swap(path: &Path) -> Option<String> {
+    fs::read_to_string(path).ok()?.strip_prefix(SWAP_HEADER).map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io;
+    use std::os::unix::fs::PermissionsExt;
+
+    use super::Buffer;
+    use crate::mods::config::{ Config, Tabs };
+    use crate::mods::data::Data;
+
+    fn buffer(file_name: &str, text: &str) -> Buffer {
+        Buffer::with_data(file_name, Data::from_text(text), &Config::with_defaults(Vec::new(), 24, 80))
+    }
+
+    const SOFT: Tabs = Tabs { width: 4, hard: false };
+    const HARD: Tabs = Tabs { width: 4, hard: true };
+
+    #[test]
+    fn new_rows_keep_the_indentation_and_add_a_level_after_blocks() {
+        let rust = buffer("a.rs", "    fn main() {\n    let x = 1;");
+        assert_eq!(rust.indent_after(0, 15, SOFT), "        ");
+        assert_eq!(rust.indent_after(0, 15, HARD), "    \t");
+        assert_eq!(rust.indent_after(1, 14, SOFT), "    ");
+        // breaking the row before the brace
+        assert_eq!(rust.indent_after(0, 13, SOFT), "    ");
+    }
+
+    #[test]
+    fn comments_after_the_code_do_not_count() {
+        let rust = buffer("a.rs", "\tif x { // then");
+        assert_eq!(rust.indent_after(0, 16, HARD), "\t\t");
+        let haskell = buffer("a.hs", "main = do -- start");
+        assert_eq!(haskell.indent_after(0, 18, Tabs { width: 2, hard: false }), "  ");
+    }
+
+    #[test]
+    fn literate_haskell_keeps_the_bird_track() {
+        let haskell = buffer("a.lhs", ">  where");
+        assert_eq!(haskell.indent_after(0, 8, Tabs { width: 2, hard: false }), ">    ");
+    }
+
+    #[test]
+    fn closing_chars_go_back_a_level() {
+        let rust = buffer("a.rs", "        \n\t\t\n      \n    x");
+        assert!(rust.dedents('}') && !buffer("a.hs", "").dedents('}'));
+        assert_eq!(rust.dedent_start(0, 8, SOFT), Some(4));
+        assert_eq!(rust.dedent_start(1, 2, HARD), Some(1));
+        // to the previous tab stop, not a whole level
+        assert_eq!(rust.dedent_start(2, 6, SOFT), Some(4));
+        assert_eq!(rust.dedent_start(3, 5, SOFT), None);
+        assert_eq!(rust.dedent_start(3, 0, SOFT), None);
+    }
+
+    #[test]
+    fn read_only_files_are_not_replaced() {
+        let name = format!("functional-editor-test-{}.txt", std::process::id());
+        let path = std::env::temp_dir().join(name);
+        fs::write(&path, "old\n").unwrap();
+        let mut permissions = fs::metadata(&path).unwrap().permissions();
+        permissions.set_readonly(true);
+        fs::set_permissions(&path, permissions).unwrap();
+
+        let config = Config::with_defaults(Vec::new(), 24, 80);
+        let mut buffer = Buffer::open(path.to_str().unwrap(), &config).unwrap();
+        buffer.data.insert_text(0, 0, "new ");
+        let error = buffer.save(false).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old\n");
+
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        buffer.save(false).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new old\n");
+        fs::remove_file(&path).unwrap();
+    }
+}
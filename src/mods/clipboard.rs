@@ -0,0 +1,110 @@
+use std::collections::VecDeque;
+
+// How many of the texts cut or copied are kept
+const RING_SIZE: usize = 16;
+
+/* The texts that were cut or copied, the last one first. Pasting takes the
+ * last one, and pasting again right after a paste goes to the older ones. */
+#[derive(Default)]
+pub struct Clipboard {
+    ring : VecDeque<Clip>,
+}
+
+/* A text of the clipboard, and whether it was taken as whole rows, which
+ * the put of modal mode puts on rows of their own. */
+struct Clip {
+    text     : String,
+    linewise : bool,
+}
+
+impl Clipboard {
+    pub fn new() -> Clipboard {
+        Default::default()
+    }
+
+    pub fn push(&mut self, text: String, linewise: bool) {
+        if text.is_empty() {
+            return;
+        }
+        self.ring.push_front(Clip { text, linewise });
+        self.ring.truncate(RING_SIZE);
+    }
+
+    // The text cut or copied age times before the last one
+    pub fn get(&self, age: usize) -> Option<&str> {
+        self.ring.get(age).map(|clip| &clip.text[..])
+    }
+
+    // Whether the last text cut or copied is made of whole rows
+    pub fn linewise(&self) -> bool {
+        self.ring.front().is_some_and(|clip| clip.linewise)
+    }
+
+    pub fn len(&self) -> usize {
+        self.ring.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ring.is_empty()
+    }
+}
+
+// Where a paste put its text, and how old the text is in the clipboard
+#[derive(Clone, Copy)]
+pub struct Paste {
+    pub start : (usize, usize),
+    pub end   : (usize, usize),
+    pub age   : usize,
+}
+
+/* The escape sequence that puts text in the clipboard of the terminal, which
+ * works through ssh as well, for the terminals that allow it (OSC 52). */
+pub fn osc52(text: &str) -> String {
+    format!("\x1b]52;c;{}\x07", base64(text.as_bytes()))
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const DIGITS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0, |group, (index, &byte)| {
+            group | (byte as u32) << (16 - 8 * index)
+        });
+        for index in 0 .. 4 {
+            if index <= chunk.len() {
+                encoded.push(DIGITS[(group >> (18 - 6 * index) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ base64, osc52 };
+
+    #[test]
+    fn base64_matches_the_rfc_vectors() {
+        // from RFC 4648, with the three kinds of padding
+        let vectors = [
+            ("", ""), ("f", "Zg=="), ("fo", "Zm8="), ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="), ("fooba", "Zm9vYmE="), ("foobar", "Zm9vYmFy"),
+        ];
+        for (text, encoded) in vectors {
+            assert_eq!(base64(text.as_bytes()), encoded);
+        }
+    }
+
+    #[test]
+    fn base64_uses_the_last_digits_for_high_bytes() {
+        assert_eq!(base64(&[0xff, 0xfe]), "//4=");
+        assert_eq!(base64("é".as_bytes()), "w6k=");
+    }
+
+    #[test]
+    fn osc52_wraps_the_encoded_text() {
+        assert_eq!(osc52("hi"), "\x1b]52;c;aGk=\x07");
+    }
+}
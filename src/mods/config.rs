@@ -1,37 +1,252 @@
-use super::lib;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use toml::Table;
+
+use super::data::Data;
+use super::grid::Style;
+use super::keymap::KeyMap;
 use super::languages::ColorsConfig;
+use super::lib;
+use super::theme::{ self, Theme, STYLE_NAMES, THEME_NAMES };
+
+/* How Tab indents the files of a language: with spaces up to the next
+ * multiple of width, or with a tab character, which is shown up to there. */
+#[derive(Clone, Copy)]
+pub struct Tabs {
+    pub width : u16,
+    pub hard  : bool,
+}
 
 pub struct Config {
-    file_name     : String,
+    file_names    : Vec<String>,
     width         : u16,
     height        : u16,
     min_col       : u16,
     min_row       : u16,
-    colors_cfg    : ColorsConfig,
+    tab_width     : u16,
+    hard_tabs     : bool,
+    // width and hardness from the [tabs.<language>] sections
+    tabs          : HashMap<String, (Option<u16>, Option<bool>)>,
+    line_numbers  : bool,
+    modal         : bool,
+    backup        : bool,
+    swap          : bool,
+    osc52         : bool,
+    keys          : KeyMap,
+    theme         : Theme,
+    // styles from the [theme] section, kept over the theme when it changes
+    theme_styles  : Vec<(&'static str, Style)>,
+    colors        : HashMap<String, HashMap<&'static str, Style>>,
 }
 
 impl Config {
-    pub fn new(args: &Vec<String>, height: u16, width: u16)
-              -> Result<Config, &'static str> {
-        if args.len() >= 3 {
-            return Err("Too many arguments! Usage: cargo run <file_name>");
+    pub fn new(args: &[String], height: u16, width: u16)
+              -> Result<Config, String> {
+        if args.iter().skip(1).any(|arg| arg.is_empty()) {
+            return Err("Invalid file name! Usage: cargo run [file_name ...]".to_string());
         }
 
-        let file_name = match args.len() {
-            2 => args[1].clone(),
-            _ => "".to_string(),
-        };
-
-        let colors_cfg = lib::get_color_config(&file_name);
+        let file_names = args.iter().skip(1).cloned().collect();
+        let mut config = Config::with_defaults(file_names, height, width);
+        if let Some(path) = Config::path().filter(|path| path.exists()) {
+            let text = fs::read_to_string(&path)
+                .map_err(|err| format!("{}: {}", path.display(), err))?;
+            config.load(&text).map_err(|err| format!("{}: {}", path.display(), err))?;
+        }
+        Ok(config)
+    }
 
-        Ok(Config {
-            file_name,
-            width,
-            height: height - 2,
+    // The settings used when the config file doesn't say otherwise
+    pub fn with_defaults(file_names: Vec<String>, height: u16, width: u16) -> Config {
+        let mut config = Config {
+            file_names,
+            width: 0,
+            height: 0,
             min_col: 4,
             min_row: 1,
-            colors_cfg,
-        })
+            tab_width: 4,
+            hard_tabs: false,
+            tabs: HashMap::new(),
+            line_numbers: true,
+            modal: false,
+            backup: false,
+            swap: true,
+            osc52: false,
+            keys: Default::default(),
+            theme: Theme::named("dark").unwrap(),
+            theme_styles: Vec::new(),
+            colors: HashMap::new(),
+        };
+        config.set_size(height, width);
+        config
+    }
+
+    // ~/.config/functional-editor/config.toml, unless XDG_CONFIG_HOME says otherwise
+    fn path() -> Option<PathBuf> {
+        let dir = env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+        Some(dir.join("functional-editor").join("config.toml"))
+    }
+
+    /* Reads the settings of a config file like this one:
+     *
+     *   [options]
+     *   tab_width = 2
+     *   hard_tabs = false
+     *   line_numbers = false
+     *   modal = true
+     *   backup = true
+     *   theme = "light"
+     *   osc52 = true
+     *
+     *   [keys]
+     *   ctrl-s = "save"
+     *
+     *   [theme]
+     *   comment = "italic #808080"
+     *
+     *   [colors.haskell]
+     *   keyword = "bold red"
+     *
+     *   [tabs.rust]
+     *   width = 8
+     *   hard = true
+     */
+    fn load(&mut self, text: &str) -> Result<(), String> {
+        let table: Table = text.parse().map_err(|err: toml::de::Error| {
+            let line = err.span().map_or(1, |span| text[.. span.start].matches('\n').count() + 1);
+            format!("line {}: {}", line, err.message().replace('\n', ", "))
+        })?;
+        for (section, value) in &table {
+            let entries = value.as_table()
+                               .ok_or(format!("'{}' should be a section", section))?;
+            match &section[..] {
+                "options" => self.load_options(entries)?,
+                "keys"    => self.load_keys(entries)?,
+                "theme"   => self.theme_styles = load_styles(entries, "theme")?,
+                "colors"  => self.load_colors(entries)?,
+                "tabs"    => self.load_tabs(entries)?,
+                _         => return Err(format!("unknown section '{}'", section)),
+            }
+        }
+        // the styles of [theme] go over the theme chosen in [options]
+        self.set_theme(self.theme.name());
+        Ok(())
+    }
+
+    fn load_options(&mut self, entries: &Table) -> Result<(), String> {
+        for (name, value) in entries {
+            let invalid = || format!("invalid value for option '{}': {}", name, value);
+            match &name[..] {
+                "tab_width" => self.tab_width = tab_width(value).ok_or_else(invalid)?,
+                "hard_tabs" => self.hard_tabs = value.as_bool().ok_or_else(invalid)?,
+                "line_numbers" => self.line_numbers = value.as_bool().ok_or_else(invalid)?,
+                "modal"        => self.modal = value.as_bool().ok_or_else(invalid)?,
+                "backup"       => self.backup = value.as_bool().ok_or_else(invalid)?,
+                "swap"         => self.swap = value.as_bool().ok_or_else(invalid)?,
+                "osc52"        => self.osc52 = value.as_bool().ok_or_else(invalid)?,
+                "theme"        => {
+                    self.theme = value.as_str()
+                                      .and_then(Theme::named)
+                                      .ok_or_else(|| format!("{}, expected one of: {}",
+                                                             invalid(), THEME_NAMES.join(", ")))?;
+                }
+                _              => return Err(format!("unknown option '{}'", name)),
+            }
+        }
+        self.min_col = if self.line_numbers { 4 } else { 1 };
+        Ok(())
+    }
+
+    fn load_keys(&mut self, entries: &Table) -> Result<(), String> {
+        for (key, value) in entries {
+            let command = value.as_str()
+                               .ok_or(format!("the command for key '{}' should be a string", key))?;
+            self.keys.bind(key, command)?;
+        }
+        Ok(())
+    }
+
+    fn load_colors(&mut self, languages: &Table) -> Result<(), String> {
+        for (language, value) in languages {
+            let entries = value.as_table()
+                               .ok_or(format!("'colors.{}' should be a section", language))?;
+            let styles = load_styles(entries, &format!("colors.{}", language))?;
+            self.colors.insert(language.clone(), styles.into_iter().collect());
+        }
+        Ok(())
+    }
+
+    fn load_tabs(&mut self, languages: &Table) -> Result<(), String> {
+        for (language, value) in languages {
+            let entries = value.as_table()
+                               .ok_or(format!("'tabs.{}' should be a section", language))?;
+            let (mut width, mut hard) = (None, None);
+            for (name, value) in entries {
+                let invalid = || format!("invalid value for '{}' in 'tabs.{}': {}",
+                                         name, language, value);
+                match &name[..] {
+                    "width" => width = Some(tab_width(value).ok_or_else(invalid)?),
+                    "hard"  => hard = Some(value.as_bool().ok_or_else(invalid)?),
+                    _       => return Err(format!("unknown setting '{}' in 'tabs.{}', \
+                                                   expected width or hard", name, language)),
+                }
+            }
+            self.tabs.insert(language.clone(), (width, hard));
+        }
+        Ok(())
+    }
+
+    /* Switches to one of the bundled themes, with the styles of the config
+     * file applied. Returns false if there is no theme with that name. */
+    pub fn set_theme(&mut self, name: &str) -> bool {
+        match Theme::named(name) {
+            Some(mut theme) => {
+                for &(kind, style) in &self.theme_styles {
+                    theme.set(kind, style);
+                }
+                self.theme = theme;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn theme(&self) -> &Theme {
+        &self.theme
+    }
+
+    /* The style of the tokens of a kind (see theme::STYLE_NAMES) in files of
+     * the language, which the config file can set apart from the theme. */
+    pub fn style(&self, language: &str, kind: &str) -> Style {
+        match self.colors.get(language).and_then(|styles| styles.get(kind)) {
+            Some(&style) => self.theme.fill(style),
+            None         => self.theme.style(kind),
+        }
+    }
+
+    // The language of a file, found from its name and from its first and last rows
+    pub fn colors_for(&self, file_name: &str, data: &Data) -> ColorsConfig {
+        const ROWS: usize = 5;
+        let len = data.len();
+        let head: Vec<String> = (0 .. len.min(ROWS)).map(|row| data.get_row(row).into_owned())
+                                                   .collect();
+        let tail: Vec<String> = (len.saturating_sub(ROWS).max(ROWS) .. len)
+                                    .map(|row| data.get_row(row).into_owned())
+                                    .collect();
+        lib::detect_language(file_name, &head, &tail)
+    }
+
+    /* Takes the size of the terminal. The last two rows are for the status
+     * line and the messages, and tiny sizes are rounded up to keep the
+     * computations of the layout valid. */
+    pub fn set_size(&mut self, height: u16, width: u16) {
+        self.height = std::cmp::max(height, 3) - 2;
+        self.width = std::cmp::max(width, self.min_col + 1);
     }
 
     pub fn height(&self) -> u16 {
@@ -41,7 +256,7 @@ impl Config {
     pub fn width(&self) -> u16 {
         self.width
     }
-    
+
     pub fn min_col(&self) -> u16 {
         self.min_col
     }
@@ -50,27 +265,107 @@ impl Config {
         self.min_row
     }
 
-    pub fn file_name(&self) -> &String {
-        &self.file_name
+    /* The tabs of a language, from its [tabs.<language>] section, then from
+     * its IndentConfig, and at last from the options. */
+    pub fn tabs(&self, colors_cfg: &ColorsConfig) -> Tabs {
+        let indent = colors_cfg.indent;
+        let default = Tabs { width: indent.tab_width.unwrap_or(self.tab_width),
+                             hard: self.hard_tabs || indent.hard_tabs };
+        match self.tabs.get(colors_cfg.language) {
+            Some(&(width, hard)) => Tabs { width: width.unwrap_or(default.width),
+                                           hard: hard.unwrap_or(default.hard) },
+            None                 => default,
+        }
     }
 
-    pub fn set_file_name(&mut self, name: &String) {
-        self.file_name = name.to_string();
-        self.colors_cfg = lib::get_color_config(name);
+    pub fn line_numbers(&self) -> bool {
+        self.line_numbers
     }
 
+    // Whether the editor starts in modal mode
+    pub fn modal(&self) -> bool {
+        self.modal
+    }
 
-    pub fn color_from_word(&self, word: &String) -> &'static str {
-        let all_digits = word.chars().all(|c| c.is_digit(10));
-        if all_digits {
-            self.colors_cfg.num_color
-        }
-        else if (self.colors_cfg.is_keyword)(word) {
-            self.colors_cfg.keyword_color
-        } else if (self.colors_cfg.is_type_name)(word) {
-            self.colors_cfg.type_name_color
-        } else {
-            self.colors_cfg.default_color
-        }
+    // Whether saving keeps the previous version of a file as file~
+    pub fn backup(&self) -> bool {
+        self.backup
+    }
+
+    // Whether unsaved changes are kept in swap files, to recover them after a crash
+    pub fn swap(&self) -> bool {
+        self.swap
+    }
+
+    // Whether what is cut or copied also goes to the clipboard of the terminal
+    pub fn osc52(&self) -> bool {
+        self.osc52
+    }
+
+    pub fn keys(&self) -> &KeyMap {
+        &self.keys
+    }
+
+    // Files given in the command line, opened in this order
+    pub fn file_names(&self) -> &Vec<String> {
+        &self.file_names
+    }
+}
+
+fn tab_width(value: &toml::Value) -> Option<u16> {
+    value.as_integer().filter(|width| (1 ..= 16).contains(width)).map(|width| width as u16)
+}
+
+// Reads the styles of a section like [theme], given by their names in STYLE_NAMES
+fn load_styles(entries: &Table, section: &str) -> Result<Vec<(&'static str, Style)>, String> {
+    let mut styles = Vec::new();
+    for (kind, value) in entries {
+        let name = STYLE_NAMES.iter()
+                              .find(|&&name| name == kind)
+                              .ok_or(format!("unknown style '{}' in '{}'", kind, section))?;
+        let style = value.as_str()
+                         .and_then(theme::parse_style)
+                         .ok_or(format!("invalid style {} for '{}', expected colors like \"red\", \
+                                         \"#ff8800\" or \"208\", then maybe \"on\" and a \
+                                         background, and bold, italic, underline or reverse",
+                                        value, kind))?;
+        styles.push((*name, style));
+    }
+    Ok(styles)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Config;
+    use crate::mods::languages;
+
+    fn tabs(config: &Config, language: &str) -> (u16, bool) {
+        let tabs = config.tabs(&languages::by_name(language).unwrap());
+        (tabs.width, tabs.hard)
+    }
+
+    #[test]
+    fn languages_have_their_usual_tabs() {
+        let config = Config::with_defaults(Vec::new(), 24, 80);
+        assert_eq!(tabs(&config, "rust"), (4, false));
+        assert_eq!(tabs(&config, "haskell"), (2, false));
+        assert_eq!(tabs(&config, "make"), (8, true));
+    }
+
+    #[test]
+    fn options_change_the_tabs_the_languages_leave_open() {
+        let mut config = Config::with_defaults(Vec::new(), 24, 80);
+        config.load("[options]\ntab_width = 3\nhard_tabs = true").unwrap();
+        assert_eq!(tabs(&config, "rust"), (3, true));
+        assert_eq!(tabs(&config, "haskell"), (2, true));
+    }
+
+    #[test]
+    fn tabs_sections_override_the_languages() {
+        let mut config = Config::with_defaults(Vec::new(), 24, 80);
+        config.load("[tabs.haskell]\nwidth = 4\n[tabs.make]\nhard = false").unwrap();
+        assert_eq!(tabs(&config, "haskell"), (4, false));
+        assert_eq!(tabs(&config, "make"), (8, false));
+        assert_eq!(tabs(&config, "rust"), (4, false));
     }
 }
@@ -1,46 +1,335 @@
+use std::borrow::Cow;
+use std::cell::{ Cell, RefCell };
+use std::fmt;
+use std::ops::Range;
+
+use ropey::Rope;
+
+use super::history::Change;
+use super::lib;
+
+/* The text of the file, kept in a rope so that edits and row lookups stay
+ * logarithmic in the size of the file. Rows are separated by '\n' and there
+ * is always at least one (possibly empty) row. Columns are counted in
+ * grapheme clusters (see lib::grapheme_count). The first row changed by an
+ * edit is remembered, for what the buffer computes from the rows, and once
+ * asked for with take_edits, the changes themselves. */
 #[derive(Clone)]
 pub struct Data {
-    info: Vec<String>,
+    text         : Rope,
+    changed_from : Cell<usize>,
+    revision     : usize,
+    edits        : RefCell<Option<Vec<Change>>>,
 }
 
 impl Data {
     pub fn from_vec(source: Vec<String>) -> Data {
-        Data { info: source }
+        Data::from_rope(Rope::from_str(&source.join("\n")))
+    }
+
+    // Builds the rows from the contents of a file, ignoring the final newline
+    pub fn from_text(source: &str) -> Data {
+        let source = source.strip_suffix('\n').unwrap_or(source);
+        if source.contains('\r') {
+            Data::from_rope(Rope::from_str(&source.replace("\r\n", "\n")))
+        } else {
+            Data::from_rope(Rope::from_str(source))
+        }
+    }
+
+    fn from_rope(text: Rope) -> Data {
+        Data { text, changed_from: Cell::new(0), revision: 0, edits: RefCell::new(None) }
+    }
+
+    /* The first row changed since the last call, or usize::MAX if there was
+     * no change. The rows before it are the same as they were then. */
+    pub fn take_changes(&self) -> usize {
+        self.changed_from.replace(usize::MAX)
+    }
+
+    // Counts the edits, so that it can be told whether the text changed since
+    pub fn revision(&self) -> usize {
+        self.revision
+    }
+
+    /* The changes made since the last call, in order, for a parser that
+     * follows the text without reading all of it again. The first call
+     * gives None, as the changes are only kept from then on. */
+    pub fn take_edits(&self) -> Option<Vec<Change>> {
+        self.edits.replace(Some(Vec::new()))
+    }
+
+    // Stops keeping the changes, until take_edits is called again
+    pub fn forget_edits(&mut self) {
+        *self.edits.get_mut() = None;
+    }
+
+    fn touch(&mut self, row: usize) {
+        self.revision += 1;
+        let changed_from = self.changed_from.get_mut();
+        *changed_from = std::cmp::min(*changed_from, row);
+    }
+
+    fn keep_edit(&mut self, change: Change) {
+        if let Some(edits) = self.edits.get_mut() {
+            edits.push(change);
+        }
     }
+
     pub fn len(&self) -> usize {
-        self.info.len()
+        self.text.len_lines()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.text.len_chars() == 0
+    }
+    pub fn row_length(&self, row: usize) -> usize {
+        lib::grapheme_count(&self.get_row(row))
+    }
+    pub fn get_row(&self, row: usize) -> Cow<'_, str> {
+        let start = self.text.line_to_char(row);
+        let slice = self.text.slice(start .. self.row_end(row));
+        match slice.as_str() {
+            Some(text) => Cow::Borrowed(text),
+            None       => Cow::Owned(slice.to_string()),
+        }
+    }
+    pub fn remove(&mut self, row: usize) {
+        if row + 1 < self.len() {
+            self.remove_text(row, 0, row + 1, 0);
+        } else if row > 0 {
+            // the last row has no newline of its own, so take the previous one
+            self.remove_text(row - 1, self.row_length(row - 1), row, self.row_length(row));
+        } else {
+            self.remove_text(row, 0, row, self.row_length(row));
+        }
+    }
+    pub fn remove_char(&mut self, row: usize, col: usize) {
+        self.remove_text(row, col, row, col + 1);
+    }
+    pub fn insert(&mut self, row: usize, text: String) {
+        if row == self.len() {
+            self.insert_text(row - 1, self.row_length(row - 1), &format!("\n{}", text));
+        } else {
+            self.insert_text(row, 0, &format!("{}\n", text));
+        }
+    }
+    pub fn insert_char(&mut self, row: usize, col: usize, c: char) {
+        self.touch(row);
+        self.text.insert_char(self.char_index(row, col), c);
+        self.keep_edit(Change::Insert { row, col, text: c.to_string() });
+    }
+    pub fn truncate_row(&mut self, row: usize, trunc_pos: usize) {
+        self.remove_text(row, trunc_pos, row, self.row_length(row));
+    }
+    pub fn extend_row(&mut self, row: usize, text: String) {
+        self.insert_text(row, self.row_length(row), &text);
+    }
+
+    // Position right after `text` once it is inserted at (row, col)
+    pub fn text_end(row: usize, col: usize, text: &str) -> (usize, usize) {
+        let lines: Vec<&str> = text.split('\n').collect();
+        let last_len = lib::grapheme_count(lines.last().unwrap());
+        if lines.len() == 1 {
+            (row, col + last_len)
+        } else {
+            (row + lines.len() - 1, last_len)
+        }
+    }
+
+    /* Inserts text (possibly with several lines) at the given position and
+     * returns the position right after it. */
+    pub fn insert_text(&mut self, row: usize, col: usize, text: &str) -> (usize, usize) {
+        self.touch(row);
+        let index = self.char_index(row, col);
+        self.text.insert(index, text);
+        self.keep_edit(Change::Insert { row, col, text: text.to_string() });
+        self.position(index + text.chars().count())
+    }
+
+    // Removes the text from (row, col) up to (end_row, end_col) and returns it
+    pub fn remove_text(&mut self, row: usize, col: usize, end_row: usize, end_col: usize) -> String {
+        self.touch(row);
+        let range = self.char_index(row, col) .. self.char_index(end_row, end_col);
+        let removed = self.text.slice(range.clone()).to_string();
+        self.text.remove(range);
+        self.keep_edit(Change::Remove { row, col, text: removed.clone() });
+        removed
+    }
+
+    // The text from (row, col) up to (end_row, end_col), left in place
+    pub fn text_between(&self, row: usize, col: usize, end_row: usize, end_col: usize) -> String {
+        self.text.slice(self.char_index(row, col) .. self.char_index(end_row, end_col)).to_string()
+    }
+
+    /* Removes as many chars as `text` has, starting at (row, col). Used to
+     * take back an insertion even if it merged with the graphemes around it. */
+    pub fn remove_inserted(&mut self, row: usize, col: usize, text: &str) {
+        self.touch(row);
+        let index = self.char_index(row, col);
+        self.text.remove(index .. index + text.chars().count());
+        self.keep_edit(Change::Remove { row, col, text: text.to_string() });
+    }
+
+    // Columns where query starts in the given row
+    pub fn matches_in_row(&self, row: usize, query: &str) -> Vec<usize> {
+        if query.is_empty() {
+            return Vec::new();
+        }
+        let text = self.get_row(row);
+        text.match_indices(query)
+            .map(|(index, _)| lib::grapheme_count(&text[.. index]))
+            .collect()
     }
-    pub fn row_length(&self, row: u16) -> usize {
-        self.info[row as usize].len()
+
+    /* Position of the first occurrence of query at or after (row, col), or of
+     * the last one before it when searching backwards. The search wraps
+     * around the ends of the text. */
+    pub fn find(&self, query: &str, row: usize, col: usize, forward: bool) -> Option<(usize, usize)> {
+        let len = self.len();
+        let rows: Vec<usize> =
+            if forward {
+                (row .. len).chain(0 ..= row).collect()
+            } else {
+                (0 ..= row).rev().chain((row .. len).rev()).collect()
+            };
+        for (i, &curr_row) in rows.iter().enumerate() {
+            let wrapped = i + 1 == rows.len();
+            let matches = self.matches_in_row(curr_row, query);
+            let found = match (forward, curr_row == row, wrapped) {
+                (true, true, false)  => matches.into_iter().find(|&c| c >= col),
+                (true, true, true)   => matches.into_iter().find(|&c| c < col),
+                (false, true, false) => matches.into_iter().rev().find(|&c| c < col),
+                (false, true, true)  => matches.into_iter().rev().find(|&c| c >= col),
+                (true, false, _)     => matches.into_iter().next(),
+                (false, false, _)    => matches.into_iter().next_back(),
+            };
+            if let Some(found_col) = found {
+                return Some((curr_row, found_col));
+            }
+        }
+        None
     }
-    pub fn get_row_mut(&mut self, row: u16) -> &mut String {
-        &mut self.info[row as usize]
+
+    // Byte offset of (row, col) in the text, and from the start of its row
+    pub fn byte_position(&self, row: usize, col: usize) -> (usize, usize) {
+        let index = self.text.char_to_byte(self.char_index(row, col));
+        (index, index - self.text.line_to_byte(row))
     }
-    pub fn get_row(&self, row: u16) -> &String {
-        &self.info[row as usize]
+
+    // Byte offsets of the start and the end of the row, before its newline
+    pub fn row_bytes(&self, row: usize) -> Range<usize> {
+        self.text.line_to_byte(row) .. self.text.char_to_byte(self.row_end(row))
     }
-    pub fn remove(&mut self, row: u16) {
-        self.info.remove(row as usize);
+
+    // The text from the byte offset to the end of the chunk of the rope it is in
+    pub fn chunk_at(&self, byte: usize) -> &str {
+        if byte >= self.text.len_bytes() {
+            return "";
+        }
+        let (chunk, start, _, _) = self.text.chunk_at_byte(byte);
+        &chunk[byte - start ..]
     }
-    pub fn remove_char(&mut self, row: u16, col: u16) {
-        self.info[row as usize].remove(col as usize);
+
+    // The text between two byte offsets, as the chunks of the rope
+    pub fn byte_chunks(&self, range: Range<usize>) -> impl Iterator<Item = &str> {
+        self.text.byte_slice(range).chunks()
     }
-    pub fn insert(&mut self, row: u16, text: String) {
-        self.info.insert(row as usize, text);
+
+    fn char_index(&self, row: usize, col: usize) -> usize {
+        let start = self.text.line_to_char(row);
+        let line = self.text.line(row);
+        // in an ASCII row every char is a grapheme, which the rope can tell without reading it
+        if line.len_bytes() == line.len_chars() {
+            return start + std::cmp::min(col, self.row_end(row) - start);
+        }
+        let text = self.get_row(row);
+        let prefix = &text[.. lib::byte_index(&text, col)];
+        start + prefix.chars().count()
     }
-    pub fn insert_char(&mut self, row: u16, col: u16, c: char) {
-        self.info[row as usize].insert(col as usize, c);
+
+    // Row and column of a char index, rounding up to the end of its grapheme
+    fn position(&self, index: usize) -> (usize, usize) {
+        let row = self.text.char_to_line(index);
+        let start = self.text.line_to_char(row);
+        let prefix = self.text.slice(start .. index).to_string();
+        (row, lib::grapheme_count(&prefix))
     }
-    pub fn truncate_row(&mut self, row: u16, trunc_pos: u16) {
-        self.info[row as usize].drain(trunc_pos as usize ..);
+
+    // Char index of the end of the row, before its newline
+    fn row_end(&self, row: usize) -> usize {
+        if row + 1 < self.len() {
+            self.text.line_to_char(row + 1) - 1
+        } else {
+            self.text.len_chars()
+        }
     }
-    pub fn extend_row(&mut self, row: u16, text: String) {
-        self.info[row as usize].extend(text.chars());
+}
+
+impl fmt::Display for Data {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for chunk in self.text.chunks() {
+            f.write_str(chunk)?;
+        }
+        writeln!(f)
     }
 }
 
-impl ToString for Data {
-    fn to_string(&self) -> String {
-        self.info.clone().join("\n") + "\n"
+#[cfg(test)]
+mod tests {
+    use super::Data;
+
+    fn rows(data: &Data) -> Vec<String> {
+        (0 .. data.len()).map(|row| data.get_row(row).into_owned()).collect()
+    }
+
+    #[test]
+    fn insert_text_within_a_row() {
+        let mut data = Data::from_text("hello world");
+        assert_eq!(data.insert_text(0, 5, ","), (0, 6));
+        assert_eq!(rows(&data), ["hello, world"]);
+    }
+
+    #[test]
+    fn insert_text_over_several_rows() {
+        let mut data = Data::from_text("first\nlast");
+        assert_eq!(data.insert_text(0, 2, "12\n34\n56"), (2, 2));
+        assert_eq!(rows(&data), ["fi12", "34", "56rst", "last"]);
+    }
+
+    #[test]
+    fn insert_text_counts_graphemes() {
+        // an e followed by a combining acute accent is a single column
+        let mut data = Data::from_text("cafe\u{301} bar");
+        assert_eq!(data.row_length(0), 8);
+        assert_eq!(data.insert_text(0, 4, "s"), (0, 5));
+        assert_eq!(rows(&data), ["cafe\u{301}s bar"]);
+        assert_eq!(data.insert_text(0, 8, "ñ\n"), (1, 0));
+        assert_eq!(rows(&data), ["cafe\u{301}s bañ", "r"]);
+    }
+
+    #[test]
+    fn remove_text_returns_what_it_removed() {
+        let mut data = Data::from_text("one\ntwo\nthree");
+        assert_eq!(data.remove_text(0, 1, 2, 2), "ne\ntwo\nth");
+        assert_eq!(rows(&data), ["oree"]);
+        assert_eq!(data.remove_text(0, 0, 0, 0), "");
+        assert_eq!(rows(&data), ["oree"]);
+    }
+
+    #[test]
+    fn remove_text_keeps_graphemes_whole() {
+        let mut data = Data::from_text("a\u{301}b\nc");
+        assert_eq!(data.remove_text(0, 0, 0, 1), "a\u{301}");
+        assert_eq!(data.remove_text(0, 1, 1, 0), "\n");
+        assert_eq!(rows(&data), ["bc"]);
+    }
+
+    #[test]
+    fn removing_the_inserted_text_restores_the_rows() {
+        let mut data = Data::from_text("ab\ncd");
+        let text = "x\u{301}\ny\n";
+        data.insert_text(1, 1, text);
+        data.remove_inserted(1, 1, text);
+        assert_eq!(rows(&data), ["ab", "cd"]);
     }
 }
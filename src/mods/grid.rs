@@ -0,0 +1,359 @@
+use std::env;
+use std::fmt::Write;
+
+use termion::{ cursor, style };
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::lib;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Color {
+    Reset,
+    // one of the 256 colors of the terminal, the first 16 being the named ones
+    Ansi(u8),
+    Rgb(u8, u8, u8),
+}
+
+// The 16 named colors, as most terminals show them
+static ANSI_RGB: [(u8, u8, u8); 16] =
+    [ (0, 0, 0),       (205, 0, 0),     (0, 205, 0),     (205, 205, 0)
+    , (0, 0, 238),     (205, 0, 205),   (0, 205, 205),   (229, 229, 229)
+    , (127, 127, 127), (255, 0, 0),     (0, 255, 0),     (255, 255, 0)
+    , (92, 92, 255),   (255, 0, 255),   (0, 255, 255),   (255, 255, 255)
+    ];
+
+// Levels of red, green and blue in the 6x6x6 cube of the 256 colors
+static CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+impl Color {
+    pub const BLACK: Color       = Color::Ansi(0);
+    pub const RED: Color         = Color::Ansi(1);
+    pub const GREEN: Color       = Color::Ansi(2);
+    pub const YELLOW: Color      = Color::Ansi(3);
+    pub const BLUE: Color        = Color::Ansi(4);
+    pub const MAGENTA: Color     = Color::Ansi(5);
+    pub const CYAN: Color        = Color::Ansi(6);
+    pub const WHITE: Color       = Color::Ansi(7);
+    pub const LIGHT_BLACK: Color = Color::Ansi(8);
+
+    fn rgb(self) -> Option<(u8, u8, u8)> {
+        match self {
+            Color::Reset                  => None,
+            Color::Rgb(r, g, b)           => Some((r, g, b)),
+            Color::Ansi(code) if code < 16  => Some(ANSI_RGB[code as usize]),
+            Color::Ansi(code) if code < 232 => {
+                let code = code as usize - 16;
+                Some((CUBE_LEVELS[code / 36], CUBE_LEVELS[code / 6 % 6], CUBE_LEVELS[code % 6]))
+            }
+            Color::Ansi(code) => {
+                let level = 8 + 10 * (code - 232);
+                Some((level, level, level))
+            }
+        }
+    }
+
+    // The closest color the terminal can show
+    pub fn downgrade(self, depth: ColorDepth) -> Color {
+        let (r, g, b) = match (self, depth) {
+            (Color::Reset, _) | (_, ColorDepth::TrueColor) => return self,
+            (Color::Ansi(code), ColorDepth::Ansi256)       => return Color::Ansi(code),
+            (Color::Ansi(code), _) if code < 16            => return Color::Ansi(code),
+            _                                              => self.rgb().unwrap(),
+        };
+        let (max, min) = (r.max(g).max(b), r.min(g).min(b));
+        if depth == ColorDepth::Ansi256 {
+            let distance = |code: u8| {
+                let (r2, g2, b2) = Color::Ansi(code).rgb().unwrap();
+                let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+                d(r, r2) + d(g, g2) + d(b, b2)
+            };
+            // the nearest level of the cube for each channel, or the nearest gray
+            let level = |v: u8| (0 .. 6).min_by_key(|&i| (CUBE_LEVELS[i] as i32 - v as i32).abs())
+                                        .unwrap() as u8;
+            let cube = 16 + 36 * level(r) + 6 * level(g) + level(b);
+            let lightness = (r as u32 + g as u32 + b as u32) / 3;
+            let gray = 232 + std::cmp::min(lightness.saturating_sub(3) / 10, 23) as u8;
+            Color::Ansi(if distance(gray) < distance(cube) { gray } else { cube })
+        } else if max - min < 40 {
+            // grays keep their lightness, as black, gray, white or bright white
+            let lightness = (r as u32 + g as u32 + b as u32) / 3;
+            Color::Ansi(match lightness { 0 ..= 63 => 0, 64 ..= 159 => 8, 160 ..= 223 => 7, _ => 15 })
+        } else {
+            // the other colors keep their hue, from the channels above the middle
+            let middle = (max as u32 + min as u32) / 2;
+            let on = |v: u8| (v as u32 > middle) as u8;
+            Color::Ansi(on(r) | on(g) << 1 | on(b) << 2)
+        }
+    }
+
+    // Parameters of the SGR sequence that sets this color, 30 for foreground and 40 for background
+    fn write_sgr(self, base: u8, out: &mut String) {
+        match self {
+            Color::Reset                   => (),
+            Color::Ansi(code) if code < 8  => write!(out, ";{}", base + code).unwrap(),
+            Color::Ansi(code) if code < 16 => write!(out, ";{}", base + 60 + code - 8).unwrap(),
+            Color::Ansi(code)              => write!(out, ";{};5;{}", base + 8, code).unwrap(),
+            Color::Rgb(r, g, b)            => write!(out, ";{};2;{};{};{}", base + 8, r, g, b).unwrap(),
+        }
+    }
+}
+
+/* How many colors the terminal can show. Colors that it can't are replaced
+ * by the closest ones it can. */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ColorDepth {
+    Ansi16,
+    Ansi256,
+    TrueColor,
+}
+
+impl ColorDepth {
+    // Guessed from the environment, as terminals have no reliable way to tell
+    pub fn detect() -> ColorDepth {
+        let colorterm = env::var("COLORTERM").unwrap_or_default();
+        let term = env::var("TERM").unwrap_or_default();
+        if colorterm == "truecolor" || colorterm == "24bit" {
+            ColorDepth::TrueColor
+        } else if term.contains("256color") || !colorterm.is_empty() {
+            ColorDepth::Ansi256
+        } else {
+            ColorDepth::Ansi16
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Style {
+    pub fg        : Color,
+    pub bg        : Color,
+    pub bold      : bool,
+    pub italic    : bool,
+    pub underline : bool,
+    pub invert    : bool,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style {
+            fg: Color::Reset,
+            bg: Color::Reset,
+            bold: false,
+            italic: false,
+            underline: false,
+            invert: false,
+        }
+    }
+}
+
+impl Style {
+    // The escape sequence that switches the terminal to this style from any other
+    fn write_to(&self, depth: ColorDepth, out: &mut String) {
+        out.push_str("\x1b[0");
+        for (on, code) in [(self.bold, 1), (self.italic, 3), (self.underline, 4), (self.invert, 7)] {
+            if on {
+                write!(out, ";{}", code).unwrap();
+            }
+        }
+        self.fg.downgrade(depth).write_sgr(30, out);
+        self.bg.downgrade(depth).write_sgr(40, out);
+        out.push('m');
+    }
+}
+
+/* A grapheme shown on the screen. The cell after a wide grapheme is covered
+ * by it, and has an empty symbol. */
+#[derive(Clone, PartialEq, Eq)]
+struct Cell {
+    symbol : String,
+    style  : Style,
+}
+
+impl Cell {
+    fn blank() -> Cell {
+        Cell { symbol: " ".to_string(), style: Default::default() }
+    }
+
+    // Never equal to a cell that is drawn, so that it is always written
+    fn unknown() -> Cell {
+        Cell { symbol: "\0".to_string(), style: Default::default() }
+    }
+}
+
+/* What the terminal should show, drawn the same way as on the terminal: by
+ * moving a pen, choosing colors and writing text. Nothing is sent to the
+ * terminal until render, which only outputs the cells that changed since
+ * the last frame. Coordinates start at 1, as in cursor::Goto. */
+pub struct Grid {
+    width  : u16,
+    height : u16,
+    cells  : Vec<Cell>,
+    shown  : Vec<Cell>,
+    pen    : (u16, u16),
+    style  : Style,
+    depth  : ColorDepth,
+}
+
+impl Grid {
+    pub fn new(height: u16, width: u16) -> Grid {
+        let mut grid = Grid {
+            width: 0,
+            height: 0,
+            cells: Vec::new(),
+            shown: Vec::new(),
+            pen: (1, 1),
+            style: Default::default(),
+            depth: ColorDepth::detect(),
+        };
+        grid.resize(height, width);
+        grid
+    }
+
+    // Starts over with a blank grid, and a terminal whose content is unknown
+    pub fn resize(&mut self, height: u16, width: u16) {
+        self.height = height;
+        self.width = width;
+        let size = height as usize * width as usize;
+        self.cells = vec![Cell::blank(); size];
+        self.shown = vec![Cell::unknown(); size];
+    }
+
+    pub fn goto(&mut self, col: u16, row: u16) {
+        self.pen = (col, row);
+    }
+
+    pub fn set_style(&mut self, style: Style) {
+        self.style = style;
+    }
+
+    pub fn set_invert(&mut self, invert: bool) {
+        self.style.invert = invert;
+    }
+
+    /* Writes text at the pen, which moves after it. Whatever goes past the
+     * right border is left out, as are the graphemes without width. */
+    pub fn write(&mut self, text: &str) {
+        let (mut col, row) = self.pen;
+        if row == 0 || row > self.height {
+            return;
+        }
+        for grapheme in text.graphemes(true) {
+            let width = lib::display_width(grapheme) as u16;
+            if width == 0 {
+                continue;
+            }
+            if col == 0 || col + width - 1 > self.width {
+                break;
+            }
+            self.set_cell(col, row, grapheme.to_string());
+            if width == 2 {
+                self.set_cell(col + 1, row, String::new());
+            }
+            col += width;
+        }
+        self.pen = (col, row);
+    }
+
+    // Fills the row with blanks from the pen to the right border
+    pub fn clear_until_newline(&mut self) {
+        let (col, row) = self.pen;
+        self.write(&" ".repeat((self.width + 1).saturating_sub(col) as usize));
+        self.pen = (col, row);
+    }
+
+    fn index(&self, col: u16, row: u16) -> usize {
+        (row as usize - 1) * self.width as usize + col as usize - 1
+    }
+
+    fn set_cell(&mut self, col: u16, row: u16, symbol: String) {
+        let index = self.index(col, row);
+        // a wide grapheme that is partly overwritten disappears entirely
+        if self.cells[index].symbol.is_empty() && col > 1 && !symbol.is_empty() {
+            self.cells[index - 1] = Cell::blank();
+        }
+        if col < self.width && self.cells[index + 1].symbol.is_empty() {
+            self.cells[index + 1] = Cell::blank();
+        }
+        self.cells[index] = Cell { symbol, style: self.style };
+    }
+
+    /* The escape sequences that bring the terminal from the last frame to
+     * this one, ending with the cursor at the given place. */
+    pub fn render(&mut self, cursor_col: u16, cursor_row: u16) -> String {
+        let mut dirty: Vec<bool> = self.cells.iter().zip(&self.shown).map(|(a, b)| a != b).collect();
+        for index in 1 .. dirty.len() {
+            // wide graphemes are written, and erased, from their first cell
+            let covered = self.cells[index].symbol.is_empty() || self.shown[index].symbol.is_empty();
+            if dirty[index] && covered {
+                dirty[index - 1] = true;
+            }
+        }
+
+        let mut out = String::new();
+        let mut pen = None;
+        let mut style = None;
+        for (index, cell) in self.cells.iter().enumerate() {
+            if !dirty[index] || cell.symbol.is_empty() {
+                continue;
+            }
+            let col = (index % self.width as usize) as u16 + 1;
+            let row = (index / self.width as usize) as u16 + 1;
+            if pen != Some((col, row)) {
+                write!(out, "{}", cursor::Goto(col, row)).unwrap();
+            }
+            if style != Some(cell.style) {
+                cell.style.write_to(self.depth, &mut out);
+                style = Some(cell.style);
+            }
+            out.push_str(&cell.symbol);
+            pen = Some((col + lib::display_width(&cell.symbol) as u16, row));
+        }
+        if style.is_some() {
+            write!(out, "{}", style::Reset).unwrap();
+        }
+        write!(out, "{}", cursor::Goto(cursor_col, cursor_row)).unwrap();
+        self.shown.clone_from(&self.cells);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ Color, ColorDepth };
+
+    #[test]
+    fn colors_are_downgraded_to_the_closest_the_terminal_has() {
+        let table = [
+            (Color::Rgb(255, 0, 0),     ColorDepth::Ansi256,   Color::Ansi(196)),
+            (Color::Rgb(0, 0, 0),       ColorDepth::Ansi256,   Color::Ansi(16)),
+            (Color::Rgb(95, 135, 175),  ColorDepth::Ansi256,   Color::Ansi(67)),
+            (Color::Rgb(255, 128, 0),   ColorDepth::Ansi256,   Color::Ansi(208)),
+            // grays are closer in the ramp than in the cube
+            (Color::Rgb(128, 128, 128), ColorDepth::Ansi256,   Color::Ansi(244)),
+            (Color::Ansi(200),          ColorDepth::Ansi256,   Color::Ansi(200)),
+            (Color::Rgb(255, 0, 0),     ColorDepth::Ansi16,    Color::RED),
+            (Color::Rgb(0, 255, 255),   ColorDepth::Ansi16,    Color::CYAN),
+            (Color::Rgb(200, 40, 190),  ColorDepth::Ansi16,    Color::MAGENTA),
+            (Color::Rgb(30, 30, 30),    ColorDepth::Ansi16,    Color::BLACK),
+            (Color::Rgb(128, 128, 128), ColorDepth::Ansi16,    Color::LIGHT_BLACK),
+            (Color::Rgb(200, 200, 200), ColorDepth::Ansi16,    Color::WHITE),
+            (Color::Rgb(250, 250, 250), ColorDepth::Ansi16,    Color::Ansi(15)),
+            (Color::Ansi(196),          ColorDepth::Ansi16,    Color::RED),
+            (Color::Ansi(244),          ColorDepth::Ansi16,    Color::LIGHT_BLACK),
+            (Color::Ansi(12),           ColorDepth::Ansi16,    Color::Ansi(12)),
+            (Color::Rgb(1, 2, 3),       ColorDepth::TrueColor, Color::Rgb(1, 2, 3)),
+            (Color::Reset,              ColorDepth::Ansi16,    Color::Reset),
+        ];
+        for (color, depth, expected) in table {
+            assert_eq!(color.downgrade(depth), expected, "{:?} in {:?}", color, depth);
+        }
+    }
+
+    #[test]
+    fn downgrading_twice_changes_nothing_more() {
+        for code in 0 ..= 255 {
+            let ansi16 = Color::Ansi(code).downgrade(ColorDepth::Ansi16);
+            assert_eq!(ansi16.downgrade(ColorDepth::Ansi16), ansi16);
+            assert!(matches!(ansi16, Color::Ansi(code) if code < 16));
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use super::data::Data;
+
+/* A reversible modification of the buffer. The text of a change may span
+ * several rows, in which case it contains '\n' separators. */
+#[derive(Clone)]
+pub enum Change {
+    Insert { row: usize, col: usize, text: String },
+    Remove { row: usize, col: usize, text: String },
+}
+
+impl Change {
+    // Applies the change to data and returns the position right after it
+    pub fn apply(&self, data: &mut Data) -> (usize, usize) {
+        match self {
+            Change::Insert { row, col, text } => data.insert_text(*row, *col, text),
+            Change::Remove { row, col, text } => {
+                data.remove_inserted(*row, *col, text);
+                (*row, *col)
+            }
+        }
+    }
+
+    pub fn inverse(&self) -> Change {
+        match self {
+            Change::Insert { row, col, text } =>
+                Change::Remove { row: *row, col: *col, text: text.clone() },
+            Change::Remove { row, col, text } =>
+                Change::Insert { row: *row, col: *col, text: text.clone() },
+        }
+    }
+}
+
+// Everything that is undone or redone by a single key press
+struct Step {
+    changes       : Vec<Change>,
+    cursor_before : (usize, usize),
+    cursor_after  : (usize, usize),
+}
+
+/* The steps that can be undone and redone. saved is the length of the undo
+ * stack when the text was last saved, or None once that text can't be
+ * reached anymore. */
+#[derive(Default)]
+pub struct History {
+    undo_stack : Vec<Step>,
+    redo_stack : Vec<Step>,
+    typing     : bool,
+    saved      : Option<usize>,
+    version    : usize,
+}
+
+impl History {
+    pub fn new() -> History {
+        History { saved: Some(0), ..Default::default() }
+    }
+
+    /* Records a change that was already applied to the buffer. Any redo
+     * history is lost, since it no longer applies to the current text. */
+    pub fn record(&mut self, change: Change, before: (usize, usize), after: (usize, usize)) {
+        self.record_group(vec![change], before, after);
+    }
+
+    // Records several changes, in the order they were applied, as a single step
+    pub fn record_group(&mut self, changes: Vec<Change>, before: (usize, usize), after: (usize, usize)) {
+        self.typing = false;
+        self.version += 1;
+        if self.saved.is_some_and(|saved| saved > self.undo_stack.len()) {
+            // the saved text was in the redo history, which is dropped
+            self.saved = None;
+        }
+        self.redo_stack.clear();
+        self.undo_stack.push(Step {
+            changes,
+            cursor_before: before,
+            cursor_after: after,
+        });
+    }
+
+    /* Like record, but consecutive typed characters are merged into the same
+     * step, so that undo removes a whole run of typing at once. */
+    pub fn record_typed(&mut self, change: Change, before: (usize, usize), after: (usize, usize)) {
+        if self.typing && self.extend_last(&change, after) {
+            self.version += 1;
+            self.redo_stack.clear();
+        } else {
+            self.record(change, before, after);
+        }
+        self.typing = true;
+    }
+
+    // Stops the next typed character from being merged into the last step
+    pub fn seal(&mut self) {
+        self.typing = false;
+    }
+
+    // Remembers the current text as the one in the file
+    pub fn mark_saved(&mut self) {
+        self.seal();
+        self.saved = Some(self.undo_stack.len());
+    }
+
+    // Whether the text differs from the one last saved
+    pub fn is_modified(&self) -> bool {
+        self.saved != Some(self.undo_stack.len())
+    }
+
+    // Changes every time the text does, to tell whether it did since some point
+    pub fn version(&self) -> usize {
+        self.version
+    }
+
+    /* Reverts the last step and returns the cursor position from before it,
+     * or None if there is nothing to undo. */
+    pub fn undo(&mut self, data: &mut Data) -> Option<(usize, usize)> {
+        self.typing = false;
+        let step = self.undo_stack.pop()?;
+        self.version += 1;
+        for change in step.changes.iter().rev() {
+            change.inverse().apply(data);
+        }
+        let cursor = step.cursor_before;
+        self.redo_stack.push(step);
+        Some(cursor)
+    }
+
+    pub fn redo(&mut self, data: &mut Data) -> Option<(usize, usize)> {
+        self.typing = false;
+        let step = self.redo_stack.pop()?;
+        self.version += 1;
+        for change in step.changes.iter() {
+            change.apply(data);
+        }
+        let cursor = step.cursor_after;
+        self.undo_stack.push(step);
+        Some(cursor)
+    }
+
+    // Appends a typed insertion to the last step if it continues it
+    fn extend_last(&mut self, change: &Change, after: (usize, usize)) -> bool {
+        let step = match self.undo_stack.last_mut() {
+            Some(step) => step,
+            None       => return false,
+        };
+        match (step.changes.last_mut(), change) {
+            (Some(Change::Insert { row, col, text }),
+             Change::Insert { row: new_row, col: new_col, text: new_text })
+                if row == new_row && !new_text.contains('\n')
+                                  && Data::text_end(*row, *col, text) == (*new_row, *new_col) => {
+                text.push_str(new_text);
+                step.cursor_after = after;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ Change, History };
+    use crate::mods::data::Data;
+
+    // Types text one character at a time, as the buffer would
+    fn type_text(history: &mut History, data: &mut Data, row: usize, col: usize, text: &str) {
+        let mut col = col;
+        for c in text.chars() {
+            let after = data.insert_text(row, col, &c.to_string());
+            history.record_typed(Change::Insert { row, col, text: c.to_string() }, (row, col), after);
+            col = after.1;
+        }
+    }
+
+    #[test]
+    fn typing_is_undone_at_once() {
+        let (mut history, mut data) = (History::new(), Data::from_text(""));
+        type_text(&mut history, &mut data, 0, 0, "hello");
+        assert_eq!(history.undo(&mut data), Some((0, 0)));
+        assert_eq!(data.get_row(0), "");
+        assert_eq!(history.undo(&mut data), None);
+        assert_eq!(history.redo(&mut data), Some((0, 5)));
+        assert_eq!(data.get_row(0), "hello");
+    }
+
+    #[test]
+    fn typing_elsewhere_starts_a_new_step() {
+        let (mut history, mut data) = (History::new(), Data::from_text(""));
+        type_text(&mut history, &mut data, 0, 0, "ab");
+        type_text(&mut history, &mut data, 0, 0, "cd");
+        assert_eq!(data.get_row(0), "cdab");
+        history.undo(&mut data);
+        assert_eq!(data.get_row(0), "ab");
+    }
+
+    #[test]
+    fn sealed_and_newline_typing_starts_a_new_step() {
+        let (mut history, mut data) = (History::new(), Data::from_text(""));
+        type_text(&mut history, &mut data, 0, 0, "ab");
+        history.seal();
+        type_text(&mut history, &mut data, 0, 2, "c");
+        type_text(&mut history, &mut data, 0, 3, "\n");
+        history.undo(&mut data);
+        assert_eq!(data.to_string(), "abc\n");
+        history.undo(&mut data);
+        assert_eq!(data.to_string(), "ab\n");
+    }
+
+    #[test]
+    fn saved_text_is_found_again_by_undo_and_redo() {
+        let (mut history, mut data) = (History::new(), Data::from_text(""));
+        assert!(!history.is_modified());
+        type_text(&mut history, &mut data, 0, 0, "ab");
+        assert!(history.is_modified());
+        history.mark_saved();
+        assert!(!history.is_modified());
+        type_text(&mut history, &mut data, 0, 2, "c");
+        assert!(history.is_modified());
+        history.undo(&mut data);
+        assert!(!history.is_modified());
+        history.undo(&mut data);
+        assert!(history.is_modified());
+        history.redo(&mut data);
+        assert!(!history.is_modified());
+    }
+
+    #[test]
+    fn saved_text_dropped_with_the_redo_history_is_lost() {
+        let (mut history, mut data) = (History::new(), Data::from_text(""));
+        type_text(&mut history, &mut data, 0, 0, "ab");
+        history.mark_saved();
+        history.undo(&mut data);
+        type_text(&mut history, &mut data, 0, 0, "x");
+        assert_eq!(history.redo(&mut data), None);
+        history.undo(&mut data);
+        assert!(history.is_modified());
+    }
+
+    #[test]
+    fn a_group_is_undone_in_reverse() {
+        let (mut history, mut data) = (History::new(), Data::from_text("abc"));
+        let removed = data.remove_text(0, 0, 0, 1);
+        data.insert_text(0, 0, "xy");
+        history.record_group(vec![Change::Remove { row: 0, col: 0, text: removed },
+                                  Change::Insert { row: 0, col: 0, text: "xy".to_string() }],
+                             (0, 0), (0, 2));
+        assert_eq!(data.get_row(0), "xybc");
+        assert_eq!(history.undo(&mut data), Some((0, 0)));
+        assert_eq!(data.get_row(0), "abc");
+    }
+}
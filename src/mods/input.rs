@@ -0,0 +1,112 @@
+use std::collections::VecDeque;
+use std::io;
+use std::sync::mpsc::{ self, Receiver, RecvTimeoutError };
+use std::thread;
+use std::time::Duration;
+
+use signal_hook::consts::{ SIGHUP, SIGQUIT, SIGTERM, SIGWINCH };
+use signal_hook::iterator::Signals;
+use termion::event::{ Event as TermEvent, Key };
+use termion::input::TermRead;
+
+// How the terminal wraps a pasted text, in bracketed paste mode
+const PASTE_START: &[u8] = b"\x1b[200~";
+const PASTE_END: &[u8] = b"\x1b[201~";
+
+/* Keys are read by a thread of their own, so that the editor can do other
+ * work while it waits for them, like writing swap files. Signals are turned
+ * into events by another thread, and come through the same channel. A text
+ * pasted in the terminal comes as a single event. */
+pub struct Input {
+    events : Receiver<Event>,
+    // keys of a paste made where text is typed key by key, like in a prompt
+    pending : VecDeque<Key>,
+}
+
+// What the user gives the editor: a key, or a text pasted at once
+pub enum Typed {
+    Key(Key),
+    Paste(String),
+}
+
+pub enum Event {
+    Key(Key),
+    Paste(String),
+    // nothing was typed for a while
+    Idle,
+    // the input was closed, so no more keys will come
+    Closed,
+    // the editor was asked to stop, for instance because its terminal was closed
+    Terminate,
+    // the size of the terminal changed
+    Resize,
+}
+
+impl Input {
+    pub fn new() -> Input {
+        let (sender, events) = mpsc::channel();
+        let key_sender = sender.clone();
+        thread::spawn(move || {
+            let mut pasted: Option<String> = None;
+            for event in io::stdin().events() {
+                let event = match event {
+                    Ok(TermEvent::Unsupported(code)) if code == PASTE_START => {
+                        pasted = Some(String::new());
+                        continue;
+                    }
+                    Ok(TermEvent::Unsupported(code)) if code == PASTE_END => match pasted.take() {
+                        Some(text) => Event::Paste(text),
+                        None       => continue,
+                    },
+                    Ok(TermEvent::Key(key)) => match &mut pasted {
+                        // the terminal only sends chars in a paste
+                        Some(text) => { if let Key::Char(c) = key { text.push(c) } continue; }
+                        None       => Event::Key(key),
+                    },
+                    Ok(_)  => continue,
+                    Err(_) => break,
+                };
+                if key_sender.send(event).is_err() {
+                    break;
+                }
+            }
+            // the signal thread keeps the channel open, so the end is told explicitly
+            let _ = key_sender.send(Event::Closed);
+        });
+        if let Ok(mut signals) = Signals::new([SIGTERM, SIGHUP, SIGQUIT, SIGWINCH]) {
+            thread::spawn(move || {
+                for signal in signals.forever() {
+                    let event = if signal == SIGWINCH { Event::Resize } else { Event::Terminate };
+                    if sender.send(event).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        Input { events, pending: VecDeque::new() }
+    }
+
+    pub fn next(&mut self, timeout: Duration) -> Event {
+        if let Some(key) = self.pending.pop_front() {
+            return Event::Key(key);
+        }
+        match self.events.recv_timeout(timeout) {
+            Ok(event)                           => event,
+            Err(RecvTimeoutError::Timeout)      => Event::Idle,
+            Err(RecvTimeoutError::Disconnected) => Event::Closed,
+        }
+    }
+}
+
+impl Input {
+    // Gives the chars of a paste as keys, before the keys typed after it
+    pub fn type_out(&mut self, text: &str) {
+        self.pending.extend(text.chars().map(Key::Char));
+    }
+}
+
+impl Default for Input {
+    fn default() -> Self {
+        Input::new()
+    }
+}
@@ -1,49 +1,144 @@
-use std::io;
-
 use termion::event::Key;
-use termion::input::TermRead;
 
+use super::input::Typed;
+use super::keymap::Command;
+use super::lib;
+use super::modal;
 use super::state::State;
 
-pub fn run(mut state: &mut State) {
-    for key in io::stdin().keys() {
+pub fn run(state: &mut State) {
+    state.present();
+    while let Some(typed) = state.next_input() {
         state.set_message("");
+        match typed {
+            Typed::Key(key)    => interpret_key(key, state),
+            Typed::Paste(text) => state.paste_text(&text),
+        }
         state.draw_status_line();
-        interpret_key(key.unwrap(), &mut state);
+        state.present();
     }
 }
 
-fn interpret_key(key: Key, state: &mut State) {
-    match key {
-        Key::Char('\x0A') => state.break_line(),
-        Key::Char('\t')   => (),
-        Key::Char(c)      => state.place_char(c),
-        Key::Backspace    => state.run_backspace(),
-        Key::Left         => state.move_cursor(0, -1),
-        Key::Right        => state.move_cursor(0, 1),
-        Key::Up           => state.move_cursor(-1, 0),
-        Key::Down         => state.move_cursor(1, 0),
-        Key::Alt('s')     => state.save_file(),
-        Key::Alt('q')     => state.die(),
-        _                 => (),
+pub fn interpret_key(key: Key, state: &mut State) {
+    if modal::interpret_key(key, state) {
+        return;
+    }
+    let command = state.command_for(key);
+    // the selection goes on while the cursor moves, and ends with the other commands
+    let keeps_mark = matches!(command, Some(Command::Left | Command::Right | Command::Up
+                                            | Command::Down | Command::Mark
+                                            | Command::Cut | Command::Copy));
+    if !keeps_mark {
+        state.clear_mark();
+    }
+    match command {
+        Some(Command::BreakLine)          => state.break_line(),
+        Some(Command::Tab)                => state.insert_tab(),
+        Some(Command::Backspace)          => state.run_backspace(),
+        Some(Command::Delete)             => state.delete_forward(),
+        Some(Command::DeleteWordBackward) => state.delete_word(false),
+        Some(Command::DeleteWordForward)  => state.delete_word(true),
+        Some(Command::KillToEnd)          => state.kill_line(true),
+        Some(Command::KillToStart)        => state.kill_line(false),
+        Some(Command::DeleteLine)         => state.delete_line(),
+        Some(Command::Left)               => state.move_cursor(0, -1),
+        Some(Command::Right)              => state.move_cursor(0, 1),
+        Some(Command::Up)                 => state.move_cursor(-1, 0),
+        Some(Command::Down)               => state.move_cursor(1, 0),
+        Some(Command::Mark)               => state.toggle_mark(),
+        Some(Command::Cut)                => state.cut(),
+        Some(Command::Copy)               => state.copy(),
+        Some(Command::Paste)              => state.paste(),
+        Some(Command::PasteOlder)         => state.paste_older(),
+        Some(Command::Undo)               => state.undo(),
+        Some(Command::Redo)               => state.redo(),
+        Some(Command::Find)               => state.find(),
+        Some(Command::Replace)            => state.replace(false),
+        Some(Command::RegexReplace)       => state.replace(true),
+        Some(Command::Save)               => state.save_file(),
+        Some(Command::Open)               => state.open_file(),
+        Some(Command::NextBuffer)         => state.cycle_buffer(1),
+        Some(Command::PreviousBuffer)     => state.cycle_buffer(-1),
+        Some(Command::PickBuffer)         => state.pick_buffer(),
+        Some(Command::CloseBuffer)        => state.close_buffer(),
+        Some(Command::PickTheme)          => state.pick_theme(),
+        Some(Command::SetLanguage)        => state.set_language(),
+        Some(Command::SplitHorizontal)    => state.split_window(false),
+        Some(Command::SplitVertical)      => state.split_window(true),
+        Some(Command::CloseWindow)        => state.close_window(),
+        Some(Command::NextWindow)         => state.next_window(),
+        Some(Command::ToggleModal)        => state.toggle_modal(),
+        Some(Command::Quit)               => state.quit(),
+        // unbound keys type their char
+        Some(Command::Nothing) | None     => if let Key::Char(c) = key { state.place_char(c) },
     }
 }
 
-pub fn run_prompt(msg: &str, state: &mut State) -> String {
-    let stdin = io::stdin();
+pub fn run_prompt(msg: &str, state: &mut State) -> Option<String> {
+    run_prompt_with(msg, state, |_, _, _| ())
+}
+
+/* Reads a line typed in the message bar. After every key press, on_key is
+ * called with the key and the text typed so far. Returns None if the prompt
+ * is cancelled with Escape. */
+pub fn run_prompt_with<F>(msg: &str, state: &mut State, mut on_key: F) -> Option<String>
+    where F: FnMut(&mut State, &str, Key) {
     let mut buffer = String::new();
     let mut pointer: usize = 0;
     state.set_message(msg);
-    for key in stdin.keys() {
-        match key.unwrap() {
-            Key::Char('\x0A') => { state.set_message(""); break; }
-            Key::Char(c)      => { buffer.insert(pointer, c); pointer += 1; }
-            Key::Left         => { if pointer > 0            { pointer -= 1; } },
-            Key::Right        => { if pointer < buffer.len() { pointer += 1; } },
-            Key::Backspace    => { if pointer > 0 { pointer -= 1; buffer.remove(pointer); } }
+    state.present();
+    while let Some(key) = state.next_key() {
+        match key {
+            Key::Char('\x0A') => { state.set_message(""); return Some(buffer); }
+            Key::Esc          => { state.set_message(""); return None; }
+            Key::Char(c) => {
+                let index = lib::byte_index(&buffer, pointer);
+                buffer.insert(index, c);
+                pointer = lib::grapheme_count(&buffer[.. index + c.len_utf8()]);
+            }
+            Key::Left      if pointer > 0 => { pointer -= 1; },
+            Key::Right     if pointer < lib::grapheme_count(&buffer) => { pointer += 1; },
+            Key::Backspace if pointer > 0 => {
+                pointer -= 1;
+                let start = lib::byte_index(&buffer, pointer);
+                buffer.replace_range(start .. lib::byte_index(&buffer, pointer + 1), "");
+            }
             _ => ()
         }
         state.set_message(&format!("{}{}", msg, buffer)[..]);
+        on_key(state, &buffer, key);
+        state.present();
+    }
+    Some(buffer)
+}
+
+/* Shows msg in the message bar and waits for a single key press, which is
+ * Escape if there are no more keys. */
+pub fn read_key(msg: &str, state: &mut State) -> Key {
+    state.set_message(msg);
+    state.present();
+    let key = state.next_key().unwrap_or(Key::Esc);
+    state.set_message("");
+    key
+}
+
+/* Lets the user choose one of items, starting from the selected one, with
+ * Up/Down and Enter. Returns None if the choice is cancelled with Escape. */
+pub fn run_picker(title: &str, items: &[String], selected: usize, state: &mut State)
+                 -> Option<usize> {
+    let mut selected = selected;
+    state.draw_picker(title, items, selected);
+    state.present();
+    while let Some(key) = state.next_key() {
+        match key {
+            Key::Char('\x0A')                         => return Some(selected),
+            Key::Esc                                  => return None,
+            Key::Up   if selected > 0                 => selected -= 1,
+            Key::Down if selected + 1 < items.len()   => selected += 1,
+            _ => (),
+        }
+        state.draw_picker(title, items, selected);
+        state.present();
     }
-    buffer
+    None
 }
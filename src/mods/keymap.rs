@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+
+use termion::event::Key;
+
+// What a key can be bound to, in the config file and in interface::interpret_key
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    BreakLine,
+    Tab,
+    Backspace,
+    Delete,
+    DeleteWordBackward,
+    DeleteWordForward,
+    KillToEnd,
+    KillToStart,
+    DeleteLine,
+    Left,
+    Right,
+    Up,
+    Down,
+    Mark,
+    Cut,
+    Copy,
+    Paste,
+    PasteOlder,
+    Undo,
+    Redo,
+    Find,
+    Replace,
+    RegexReplace,
+    Save,
+    Open,
+    NextBuffer,
+    PreviousBuffer,
+    PickBuffer,
+    CloseBuffer,
+    PickTheme,
+    SetLanguage,
+    SplitHorizontal,
+    SplitVertical,
+    CloseWindow,
+    NextWindow,
+    ToggleModal,
+    Quit,
+    // leaves the key unbound, for instance to type it as a char
+    Nothing,
+}
+
+// Names of the commands in the config file
+static COMMAND_NAMES: [(&str, Command); 38] =
+    [ ("break_line",           Command::BreakLine)
+    , ("tab",                  Command::Tab)
+    , ("backspace",            Command::Backspace)
+    , ("delete",               Command::Delete)
+    , ("delete_word_backward", Command::DeleteWordBackward)
+    , ("delete_word_forward",  Command::DeleteWordForward)
+    , ("kill_to_end",          Command::KillToEnd)
+    , ("kill_to_start",        Command::KillToStart)
+    , ("delete_line",          Command::DeleteLine)
+    , ("left",                 Command::Left)
+    , ("right",                Command::Right)
+    , ("up",                   Command::Up)
+    , ("down",                 Command::Down)
+    , ("mark",                 Command::Mark)
+    , ("cut",                  Command::Cut)
+    , ("copy",                 Command::Copy)
+    , ("paste",                Command::Paste)
+    , ("paste_older",          Command::PasteOlder)
+    , ("undo",                 Command::Undo)
+    , ("redo",                 Command::Redo)
+    , ("find",                 Command::Find)
+    , ("replace",              Command::Replace)
+    , ("regex_replace",        Command::RegexReplace)
+    , ("save",                 Command::Save)
+    , ("open",                 Command::Open)
+    , ("next_buffer",          Command::NextBuffer)
+    , ("previous_buffer",      Command::PreviousBuffer)
+    , ("pick_buffer",          Command::PickBuffer)
+    , ("close_buffer",         Command::CloseBuffer)
+    , ("pick_theme",           Command::PickTheme)
+    , ("set_language",         Command::SetLanguage)
+    , ("split_horizontal",     Command::SplitHorizontal)
+    , ("split_vertical",       Command::SplitVertical)
+    , ("close_window",         Command::CloseWindow)
+    , ("next_window",          Command::NextWindow)
+    , ("toggle_modal",         Command::ToggleModal)
+    , ("quit",                 Command::Quit)
+    , ("nothing",              Command::Nothing)
+    ];
+
+impl Command {
+    pub fn from_name(name: &str) -> Option<Command> {
+        COMMAND_NAMES.iter().find(|(known, _)| *known == name).map(|&(_, command)| command)
+    }
+}
+
+/* Reads a key written like "ctrl-f", "alt-q", "enter", "left" or "x". Ctrl
+ * and Alt only combine with a single char, as the terminal can't tell them
+ * apart otherwise, and with space for ctrl. */
+pub fn parse_key(name: &str) -> Option<Key> {
+    let single = |text: &str| {
+        let mut chars = text.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _               => None,
+        }
+    };
+    if name == "ctrl-space" {
+        return Some(Key::Null);
+    }
+    if let Some(rest) = name.strip_prefix("ctrl-") {
+        return single(rest).map(|c| Key::Ctrl(c.to_ascii_lowercase()));
+    }
+    if let Some(rest) = name.strip_prefix("alt-") {
+        return single(rest).map(Key::Alt);
+    }
+    let key = match name {
+        "enter"     => Key::Char('\n'),
+        "tab"       => Key::Char('\t'),
+        "backspace" => Key::Backspace,
+        "delete"    => Key::Delete,
+        "esc"       => Key::Esc,
+        "left"      => Key::Left,
+        "right"     => Key::Right,
+        "up"        => Key::Up,
+        "down"      => Key::Down,
+        "home"      => Key::Home,
+        "end"       => Key::End,
+        "pageup"    => Key::PageUp,
+        "pagedown"  => Key::PageDown,
+        _           => return single(name).map(Key::Char),
+    };
+    Some(key)
+}
+
+/* The command of each key. Keys without one type their char, if they have
+ * one, and are ignored otherwise. */
+pub struct KeyMap {
+    bindings : HashMap<Key, Command>,
+}
+
+impl KeyMap {
+    pub fn get(&self, key: Key) -> Option<Command> {
+        self.bindings.get(&key).copied()
+    }
+
+    // Binds the key to the command, both given by their names in the config file
+    pub fn bind(&mut self, key: &str, command: &str) -> Result<(), String> {
+        let parsed_key = parse_key(key).ok_or(format!("unknown key '{}'", key))?;
+        let parsed_command = Command::from_name(command)
+            .ok_or(format!("unknown command '{}' for key '{}'", command, key))?;
+        self.bindings.insert(parsed_key, parsed_command);
+        Ok(())
+    }
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        let bindings = [
+            (Key::Char('\n'), Command::BreakLine),
+            (Key::Char('\t'), Command::Tab),
+            (Key::Backspace,  Command::Backspace),
+            (Key::Delete,     Command::Delete),
+            (Key::Ctrl('w'),  Command::DeleteWordBackward),
+            (Key::Alt('d'),   Command::DeleteWordForward),
+            (Key::Ctrl('k'),  Command::KillToEnd),
+            (Key::Ctrl('u'),  Command::KillToStart),
+            (Key::Alt('k'),   Command::DeleteLine),
+            (Key::Left,       Command::Left),
+            (Key::Right,      Command::Right),
+            (Key::Up,         Command::Up),
+            (Key::Down,       Command::Down),
+            (Key::Null,       Command::Mark),
+            (Key::Ctrl('x'),  Command::Cut),
+            (Key::Ctrl('c'),  Command::Copy),
+            (Key::Ctrl('v'),  Command::Paste),
+            (Key::Alt('y'),   Command::PasteOlder),
+            (Key::Ctrl('z'),  Command::Undo),
+            (Key::Ctrl('y'),  Command::Redo),
+            (Key::Ctrl('f'),  Command::Find),
+            (Key::Ctrl('r'),  Command::Replace),
+            (Key::Alt('r'),   Command::RegexReplace),
+            (Key::Alt('s'),   Command::Save),
+            (Key::Alt('o'),   Command::Open),
+            (Key::Alt('n'),   Command::NextBuffer),
+            (Key::Alt('p'),   Command::PreviousBuffer),
+            (Key::Alt('b'),   Command::PickBuffer),
+            (Key::Alt('w'),   Command::CloseBuffer),
+            (Key::Alt('t'),   Command::PickTheme),
+            (Key::Alt('l'),   Command::SetLanguage),
+            (Key::Alt('-'),   Command::SplitHorizontal),
+            (Key::Alt('|'),   Command::SplitVertical),
+            (Key::Alt('x'),   Command::CloseWindow),
+            (Key::Alt('.'),   Command::NextWindow),
+            (Key::Alt('m'),   Command::ToggleModal),
+            (Key::Alt('q'),   Command::Quit),
+        ];
+        KeyMap { bindings: bindings.iter().copied().collect() }
+    }
+}
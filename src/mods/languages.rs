@@ -1,25 +1,180 @@
+pub mod agda;
+pub mod clojure;
+pub mod conf;
+pub mod elm;
+pub mod fsharp;
 pub mod haskell;
+pub mod idris;
+pub mod lean;
+pub mod lisp;
+pub mod ocaml;
+pub mod purescript;
 pub mod rust;
+pub mod scala;
 
+use super::lexer::Token;
+
+// Names of the languages, as given to by_name
+pub static LANGUAGE_NAMES: [&str; 18] =
+    [ "text"
+    , "agda"
+    , "cabal"
+    , "clojure"
+    , "elm"
+    , "fsharp"
+    , "haskell"
+    , "idris"
+    , "lean"
+    , "lisp"
+    , "literate_haskell"
+    , "make"
+    , "ocaml"
+    , "purescript"
+    , "rust"
+    , "scala"
+    , "toml"
+    , "yaml"
+    ];
+
+pub fn by_name(name: &str) -> Option<ColorsConfig> {
+    let colors_cfg = match name {
+        "text"             => Default::default(),
+        "agda"             => agda::AgdaConfig,
+        "cabal"            => conf::CabalConfig,
+        "clojure"          => clojure::ClojureConfig,
+        "elm"              => elm::ElmConfig,
+        "fsharp"           => fsharp::FSharpConfig,
+        "haskell"          => haskell::HaskellConfig,
+        "idris"            => idris::IdrisConfig,
+        "lean"             => lean::LeanConfig,
+        "lisp"             => lisp::LispConfig,
+        "literate_haskell" => haskell::LiterateHaskellConfig,
+        "make"             => conf::MakeConfig,
+        "ocaml"            => ocaml::OCamlConfig,
+        "purescript"       => purescript::PureScriptConfig,
+        "rust"             => rust::RustConfig,
+        "scala"            => scala::ScalaConfig,
+        "toml"             => conf::TomlConfig,
+        "yaml"             => conf::YamlConfig,
+        _                  => return None,
+    };
+    Some(colors_cfg)
+}
+
+/* How the files of a language are highlighted: what the lexer needs to know
+ * to find the tokens, and which words are keywords and type names. */
 #[derive(Clone, Copy)]
 pub struct ColorsConfig {
-    pub num_color       : &'static str,
-    pub type_name_color : &'static str,
-    pub keyword_color   : &'static str,
-    pub default_color   : &'static str,
-    pub is_type_name    : fn(&str) -> bool,
-    pub is_keyword      : fn(&str) -> bool,
+    pub language           : &'static str,
+    pub is_type_name       : fn(&str) -> bool,
+    pub is_keyword         : fn(&str) -> bool,
+    pub line_comment       : Option<&'static str>,
+    pub block_comment      : Option<(&'static str, &'static str)>,
+    // whether the line comment marker can start an operator, like --> in Haskell
+    pub symbolic_comments  : bool,
+    pub string_quote       : Option<char>,
+    pub char_quote         : Option<char>,
+    pub multi_line_strings : bool,
+    // chars other than letters, digits and '_' that can be part of a word
+    pub word_chars         : &'static str,
+    // whether only the rows starting with '>' are code, as in literate Haskell
+    pub bird_tracks        : bool,
+    pub indent             : IndentConfig,
+}
+
+impl ColorsConfig {
+    // Name of the style of a token in the theme (see theme::STYLE_NAMES)
+    pub fn style_of(&self, token: Token, text: &str) -> &'static str {
+        match token {
+            // plain text is not highlighted
+            _ if self.language == "text"             => "default",
+            Token::Word if (self.is_keyword)(text)   => "keyword",
+            Token::Word if (self.is_type_name)(text) => "type_name",
+            Token::Number                            => "number",
+            Token::Comment                           => "comment",
+            Token::String                            => "string",
+            Token::Operator                          => "operator",
+            _                                        => "default",
+        }
+    }
 }
 
 impl Default for ColorsConfig {
     fn default() -> Self {
         ColorsConfig {
-            num_color       : "white",
-            type_name_color : "white",
-            keyword_color   : "white",
-            default_color   : "white",
-            is_type_name    : |_| false,
-            is_keyword      : |_| false,
+            language           : "text",
+            is_type_name       : |_| false,
+            is_keyword         : |_| false,
+            line_comment       : None,
+            block_comment      : None,
+            symbolic_comments  : false,
+            string_quote       : None,
+            char_quote         : None,
+            multi_line_strings : false,
+            word_chars         : "",
+            bird_tracks        : false,
+            indent             : PlainIndent,
+        }
+    }
+}
+
+/* What the rows of a language are indented after, besides the indentation
+ * of the row above. A row whose code ends with something that opens_block
+ * is followed by one more level, and one of the closing chars typed first
+ * in a row takes a level off. A [tabs.<language>] section of the options
+ * overrides the tabs. */
+#[derive(Clone, Copy)]
+pub struct IndentConfig {
+    pub opens_block : fn(&str) -> bool,
+    pub closing     : &'static str,
+    // the usual width of a level in the language, otherwise the one of the options
+    pub tab_width   : Option<u16>,
+    // whether the language needs tabs, whatever the options say
+    pub hard_tabs   : bool,
+}
+
+// For the languages without rules, indented as the options say
+#[allow(non_upper_case_globals)]
+pub static PlainIndent: IndentConfig = IndentConfig {
+    opens_block : |_| false,
+    closing     : "",
+    tab_width   : None,
+    hard_tabs   : false,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::by_name;
+
+    fn opens_block(language: &str, code: &str) -> bool {
+        (by_name(language).unwrap().indent.opens_block)(code)
+    }
+
+    #[test]
+    fn haskell_blocks_open_after_layout_keywords_and_equations() {
+        for code in ["main = do", "  where", "f x =", "case x of", "g = \\case", "(do", "let"] {
+            assert!(opens_block("haskell", code), "{}", code);
+        }
+        for code in ["x == y", "let x = 1", "import Data.List", "window", ""] {
+            assert!(!opens_block("haskell", code), "{}", code);
         }
     }
+
+    #[test]
+    fn rust_blocks_open_after_brackets() {
+        for code in ["fn main() {", "foo(", "let v = vec!["] {
+            assert!(opens_block("rust", code), "{}", code);
+        }
+        for code in ["let x = 1;", "}", "x = y", "do"] {
+            assert!(!opens_block("rust", code), "{}", code);
+        }
+    }
+
+    #[test]
+    fn closing_brackets_dedent_only_where_blocks_are_brackets() {
+        let rust = by_name("rust").unwrap().indent;
+        assert!(rust.closing.contains('}') && rust.closing.contains(')'));
+        assert!(by_name("haskell").unwrap().indent.closing.is_empty());
+        assert!(by_name("text").unwrap().indent.closing.is_empty());
+    }
 }
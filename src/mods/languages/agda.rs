@@ -0,0 +1,60 @@
+use super::{ haskell, ColorsConfig };
+
+#[allow(non_upper_case_globals)]
+static AgdaKeywords: [&str; 39] =
+    [ "abstract"
+    , "constructor"
+    , "data"
+    , "do"
+    , "eta-equality"
+    , "field"
+    , "forall"
+    , "hiding"
+    , "import"
+    , "in"
+    , "inductive"
+    , "infix"
+    , "infixl"
+    , "infixr"
+    , "instance"
+    , "let"
+    , "macro"
+    , "module"
+    , "mutual"
+    , "no-eta-equality"
+    , "open"
+    , "overlap"
+    , "pattern"
+    , "postulate"
+    , "primitive"
+    , "private"
+    , "public"
+    , "quote"
+    , "record"
+    , "renaming"
+    , "rewrite"
+    , "syntax"
+    , "tactic"
+    , "to"
+    , "unquote"
+    , "using"
+    , "variable"
+    , "where"
+    , "with"
+    ];
+
+#[allow(non_upper_case_globals)]
+pub static AgdaConfig: ColorsConfig = ColorsConfig {
+    language           : "agda",
+    is_keyword         : |word| AgdaKeywords.contains(&word),
+    is_type_name       : |word| word.starts_with(char::is_uppercase),
+    line_comment       : Some("--"),
+    block_comment      : Some(("{-", "-}")),
+    symbolic_comments  : true,
+    string_quote       : Some('"'),
+    char_quote         : Some('\''),
+    multi_line_strings : false,
+    word_chars         : "'-",
+    bird_tracks        : false,
+    indent             : haskell::HaskellIndent,
+};
@@ -0,0 +1,56 @@
+use super::{ ColorsConfig, IndentConfig, PlainIndent };
+
+#[allow(non_upper_case_globals)]
+static ClojureKeywords: [&str; 34] =
+    [ "case"
+    , "catch"
+    , "cond"
+    , "def"
+    , "defmacro"
+    , "defmethod"
+    , "defmulti"
+    , "defn"
+    , "defn-"
+    , "defprotocol"
+    , "defrecord"
+    , "deftype"
+    , "do"
+    , "doseq"
+    , "dotimes"
+    , "finally"
+    , "fn"
+    , "for"
+    , "if"
+    , "if-let"
+    , "if-not"
+    , "let"
+    , "letfn"
+    , "loop"
+    , "new"
+    , "ns"
+    , "quote"
+    , "recur"
+    , "set!"
+    , "throw"
+    , "try"
+    , "when"
+    , "when-let"
+    , "when-not"
+    ];
+
+// Words like defn- and set! are read as a whole, as symbols can have those chars
+#[allow(non_upper_case_globals)]
+pub static ClojureConfig: ColorsConfig = ColorsConfig {
+    language           : "clojure",
+    is_keyword         : |word| ClojureKeywords.contains(&word),
+    is_type_name       : |word| word.starts_with(char::is_uppercase),
+    line_comment       : Some(";"),
+    block_comment      : None,
+    symbolic_comments  : false,
+    string_quote       : Some('"'),
+    char_quote         : None,
+    multi_line_strings : true,
+    word_chars         : "-?!*<>=/+.'",
+    bird_tracks        : false,
+    indent             : IndentConfig { tab_width: Some(2), ..PlainIndent },
+};
@@ -0,0 +1,70 @@
+use super::{ ColorsConfig, IndentConfig, PlainIndent };
+
+// Build and configuration files, highlighted for their comments, strings and numbers
+
+#[allow(non_upper_case_globals)]
+static MakeKeywords: [&str; 12] =
+    [ "define"
+    , "else"
+    , "endef"
+    , "endif"
+    , "export"
+    , "ifdef"
+    , "ifeq"
+    , "ifndef"
+    , "ifneq"
+    , "include"
+    , "override"
+    , "unexport"
+    ];
+
+#[allow(non_upper_case_globals)]
+pub static MakeConfig: ColorsConfig = ColorsConfig {
+    language           : "make",
+    is_keyword         : |word| MakeKeywords.contains(&word),
+    is_type_name       : |_| false,
+    line_comment       : Some("#"),
+    block_comment      : None,
+    symbolic_comments  : false,
+    string_quote       : Some('"'),
+    char_quote         : None,
+    multi_line_strings : false,
+    word_chars         : "-",
+    bird_tracks        : false,
+    // recipes of makefiles have to start with a tab
+    indent             : IndentConfig { tab_width: Some(8), hard_tabs: true, ..PlainIndent },
+};
+
+#[allow(non_upper_case_globals)]
+pub static YamlConfig: ColorsConfig = ColorsConfig {
+    language           : "yaml",
+    is_keyword         : |word| ["true", "false", "null", "yes", "no"].contains(&word),
+    is_type_name       : |_| false,
+    line_comment       : Some("#"),
+    block_comment      : None,
+    symbolic_comments  : false,
+    string_quote       : Some('"'),
+    char_quote         : None,
+    multi_line_strings : false,
+    word_chars         : "-",
+    bird_tracks        : false,
+    indent             : IndentConfig { tab_width: Some(2), ..PlainIndent },
+};
+
+#[allow(non_upper_case_globals)]
+pub static TomlConfig: ColorsConfig = ColorsConfig {
+    language           : "toml",
+    is_keyword         : |word| ["true", "false"].contains(&word),
+    indent             : PlainIndent,
+    ..YamlConfig
+};
+
+// The .cabal files and cabal.project, whose comments are the ones of Haskell
+#[allow(non_upper_case_globals)]
+pub static CabalConfig: ColorsConfig = ColorsConfig {
+    language           : "cabal",
+    is_keyword         : |word| ["if", "else", "flag", "impl", "os", "arch"].contains(&word),
+    line_comment       : Some("--"),
+    symbolic_comments  : true,
+    ..YamlConfig
+};
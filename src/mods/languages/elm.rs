@@ -0,0 +1,43 @@
+use super::{ haskell, ColorsConfig, IndentConfig };
+
+#[allow(non_upper_case_globals)]
+static ElmKeywords: [&str; 15] =
+    [ "alias"
+    , "as"
+    , "case"
+    , "else"
+    , "exposing"
+    , "if"
+    , "import"
+    , "in"
+    , "let"
+    , "module"
+    , "of"
+    , "port"
+    , "then"
+    , "type"
+    , "where"
+    ];
+
+#[allow(non_upper_case_globals)]
+pub static ElmConfig: ColorsConfig = ColorsConfig {
+    language           : "elm",
+    is_keyword         : |word| ElmKeywords.contains(&word),
+    is_type_name       : |word| word.starts_with(char::is_uppercase),
+    line_comment       : Some("--"),
+    block_comment      : Some(("{-", "-}")),
+    symbolic_comments  : true,
+    string_quote       : Some('"'),
+    char_quote         : Some('\''),
+    multi_line_strings : false,
+    word_chars         : "",
+    bird_tracks        : false,
+    indent             : ElmIndent,
+};
+
+// The layout rules of Haskell, but elm-format indents with the usual width
+#[allow(non_upper_case_globals)]
+pub static ElmIndent: IndentConfig = IndentConfig {
+    tab_width : None,
+    ..haskell::HaskellIndent
+};
@@ -0,0 +1,108 @@
+use super::{ ColorsConfig, PlainIndent };
+
+#[allow(non_upper_case_globals)]
+static FSharpKeywords: [&str; 63] =
+    [ "abstract"
+    , "and"
+    , "as"
+    , "assert"
+    , "base"
+    , "begin"
+    , "class"
+    , "default"
+    , "delegate"
+    , "do"
+    , "done"
+    , "downcast"
+    , "downto"
+    , "elif"
+    , "else"
+    , "end"
+    , "exception"
+    , "extern"
+    , "false"
+    , "finally"
+    , "for"
+    , "fun"
+    , "function"
+    , "global"
+    , "if"
+    , "in"
+    , "inherit"
+    , "inline"
+    , "interface"
+    , "internal"
+    , "lazy"
+    , "let"
+    , "match"
+    , "member"
+    , "module"
+    , "mutable"
+    , "namespace"
+    , "new"
+    , "not"
+    , "null"
+    , "of"
+    , "open"
+    , "or"
+    , "override"
+    , "private"
+    , "public"
+    , "rec"
+    , "return"
+    , "static"
+    , "struct"
+    , "then"
+    , "to"
+    , "true"
+    , "try"
+    , "type"
+    , "upcast"
+    , "use"
+    , "val"
+    , "void"
+    , "when"
+    , "while"
+    , "with"
+    , "yield"
+    ];
+
+#[allow(non_upper_case_globals)]
+static FSharpTypes: [&str; 20] =
+    [ "bool"
+    , "byte"
+    , "char"
+    , "decimal"
+    , "double"
+    , "float"
+    , "float32"
+    , "int"
+    , "int16"
+    , "int64"
+    , "list"
+    , "option"
+    , "sbyte"
+    , "seq"
+    , "single"
+    , "string"
+    , "uint"
+    , "uint16"
+    , "uint64"
+    , "unit"
+    ];
+
+#[allow(non_upper_case_globals)]
+pub static FSharpConfig: ColorsConfig = ColorsConfig {
+    language           : "fsharp",
+    is_keyword         : |word| FSharpKeywords.contains(&word),
+    is_type_name       : |word| FSharpTypes.contains(&word) || word.starts_with(char::is_uppercase),
+    line_comment       : Some("//"),
+    block_comment      : Some(("(*", "*)")),
+    symbolic_comments  : false,
+    string_quote       : Some('"'),
+    char_quote         : Some('\''),
+    multi_line_strings : true,
+    word_chars         : "'",
+    bird_tracks        : false,
+    indent             : PlainIndent,
+};
@@ -1,7 +1,7 @@
-use super::ColorsConfig;
+use super::{ ColorsConfig, IndentConfig };
 
 #[allow(non_upper_case_globals)]
-static HaskellKeywords: [&'static str; 24] =
+static HaskellKeywords: [&str; 24] =
     [ "case"
     , "class"
     , "data"
@@ -30,10 +30,37 @@ static HaskellKeywords: [&'static str; 24] =
 
 #[allow(non_upper_case_globals)]
 pub static HaskellConfig: ColorsConfig = ColorsConfig {
-    is_keyword      : |word| HaskellKeywords.contains(&word),
-    is_type_name    : |word| !word.is_empty() && word.chars().next().unwrap().is_uppercase(),
-    num_color       : "red",
-    type_name_color : "blue",
-    keyword_color   : "green",
-    default_color   : "white",
+    language           : "haskell",
+    is_keyword         : |word| HaskellKeywords.contains(&word),
+    is_type_name       : |word| !word.is_empty() && word.chars().next().unwrap().is_uppercase(),
+    line_comment       : Some("--"),
+    block_comment      : Some(("{-", "-}")),
+    symbolic_comments  : true,
+    string_quote       : Some('"'),
+    char_quote         : Some('\''),
+    multi_line_strings : false,
+    word_chars         : "'",
+    bird_tracks        : false,
+    indent             : HaskellIndent,
+};
+
+// Literate Haskell with bird tracks, where the rows of code start with '>'
+#[allow(non_upper_case_globals)]
+pub static LiterateHaskellConfig: ColorsConfig = ColorsConfig {
+    language    : "literate_haskell",
+    bird_tracks : true,
+    ..HaskellConfig
+};
+
+/* The layout keywords open a block on the next row, and so does an equation
+ * whose right side starts there. */
+#[allow(non_upper_case_globals)]
+pub static HaskellIndent: IndentConfig = IndentConfig {
+    opens_block : |code| {
+        let last = code.rsplit(|c: char| c.is_whitespace() || "()[]{},;".contains(c)).next();
+        matches!(last, Some("where" | "let" | "do" | "mdo" | "of" | "rec" | "=" | "\\case"))
+    },
+    closing     : "",
+    tab_width   : Some(2),
+    hard_tabs   : false,
 };
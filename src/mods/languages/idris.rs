@@ -0,0 +1,56 @@
+use super::{ haskell, ColorsConfig };
+
+#[allow(non_upper_case_globals)]
+static IdrisKeywords: [&str; 35] =
+    [ "auto"
+    , "case"
+    , "codata"
+    , "covering"
+    , "data"
+    , "default"
+    , "do"
+    , "else"
+    , "export"
+    , "if"
+    , "implementation"
+    , "implicit"
+    , "import"
+    , "impossible"
+    , "in"
+    , "infix"
+    , "infixl"
+    , "infixr"
+    , "interface"
+    , "let"
+    , "module"
+    , "mutual"
+    , "namespace"
+    , "of"
+    , "parameters"
+    , "partial"
+    , "private"
+    , "public"
+    , "record"
+    , "rewrite"
+    , "then"
+    , "total"
+    , "using"
+    , "where"
+    , "with"
+    ];
+
+#[allow(non_upper_case_globals)]
+pub static IdrisConfig: ColorsConfig = ColorsConfig {
+    language           : "idris",
+    is_keyword         : |word| IdrisKeywords.contains(&word),
+    is_type_name       : |word| word.starts_with(char::is_uppercase),
+    line_comment       : Some("--"),
+    block_comment      : Some(("{-", "-}")),
+    symbolic_comments  : true,
+    string_quote       : Some('"'),
+    char_quote         : Some('\''),
+    multi_line_strings : false,
+    word_chars         : "'",
+    bird_tracks        : false,
+    indent             : haskell::HaskellIndent,
+};
@@ -0,0 +1,58 @@
+use super::{ ColorsConfig, IndentConfig, PlainIndent };
+
+#[allow(non_upper_case_globals)]
+static LeanKeywords: [&str; 37] =
+    [ "abbrev"
+    , "at"
+    , "axiom"
+    , "by"
+    , "calc"
+    , "class"
+    , "def"
+    , "deriving"
+    , "do"
+    , "else"
+    , "end"
+    , "example"
+    , "fun"
+    , "have"
+    , "if"
+    , "import"
+    , "in"
+    , "inductive"
+    , "instance"
+    , "let"
+    , "match"
+    , "mutual"
+    , "namespace"
+    , "noncomputable"
+    , "open"
+    , "private"
+    , "protected"
+    , "section"
+    , "show"
+    , "structure"
+    , "syntax"
+    , "termination"
+    , "then"
+    , "theorem"
+    , "variable"
+    , "where"
+    , "with"
+    ];
+
+#[allow(non_upper_case_globals)]
+pub static LeanConfig: ColorsConfig = ColorsConfig {
+    language           : "lean",
+    is_keyword         : |word| LeanKeywords.contains(&word),
+    is_type_name       : |word| word.starts_with(char::is_uppercase),
+    line_comment       : Some("--"),
+    block_comment      : Some(("/-", "-/")),
+    symbolic_comments  : false,
+    string_quote       : Some('"'),
+    char_quote         : Some('\''),
+    multi_line_strings : false,
+    word_chars         : "'!?",
+    bird_tracks        : false,
+    indent             : IndentConfig { tab_width: Some(2), ..PlainIndent },
+};
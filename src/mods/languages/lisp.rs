@@ -0,0 +1,49 @@
+use super::{ ColorsConfig, IndentConfig, PlainIndent };
+
+#[allow(non_upper_case_globals)]
+static LispKeywords: [&str; 27] =
+    [ "and"
+    , "begin"
+    , "case"
+    , "cond"
+    , "define"
+    , "define-syntax"
+    , "defmacro"
+    , "defparameter"
+    , "defun"
+    , "defvar"
+    , "do"
+    , "dolist"
+    , "dotimes"
+    , "else"
+    , "if"
+    , "lambda"
+    , "let"
+    , "let*"
+    , "letrec"
+    , "loop"
+    , "or"
+    , "quote"
+    , "set!"
+    , "setf"
+    , "setq"
+    , "unless"
+    , "when"
+    ];
+
+// Common Lisp, Scheme and Racket, which share their comments and most of their special forms
+#[allow(non_upper_case_globals)]
+pub static LispConfig: ColorsConfig = ColorsConfig {
+    language           : "lisp",
+    is_keyword         : |word| LispKeywords.contains(&word),
+    is_type_name       : |_| false,
+    line_comment       : Some(";"),
+    block_comment      : Some(("#|", "|#")),
+    symbolic_comments  : false,
+    string_quote       : Some('"'),
+    char_quote         : None,
+    multi_line_strings : true,
+    word_chars         : "-?!*<>=/+",
+    bird_tracks        : false,
+    indent             : IndentConfig { tab_width: Some(2), ..PlainIndent },
+};
@@ -0,0 +1,88 @@
+use super::{ ColorsConfig, IndentConfig, PlainIndent };
+
+#[allow(non_upper_case_globals)]
+static OCamlKeywords: [&str; 48] =
+    [ "and"
+    , "as"
+    , "assert"
+    , "begin"
+    , "class"
+    , "constraint"
+    , "do"
+    , "done"
+    , "downto"
+    , "else"
+    , "end"
+    , "exception"
+    , "external"
+    , "false"
+    , "for"
+    , "fun"
+    , "function"
+    , "functor"
+    , "if"
+    , "in"
+    , "include"
+    , "inherit"
+    , "initializer"
+    , "lazy"
+    , "let"
+    , "match"
+    , "method"
+    , "module"
+    , "mutable"
+    , "new"
+    , "nonrec"
+    , "object"
+    , "of"
+    , "open"
+    , "private"
+    , "rec"
+    , "sig"
+    , "struct"
+    , "then"
+    , "to"
+    , "true"
+    , "try"
+    , "type"
+    , "val"
+    , "virtual"
+    , "when"
+    , "while"
+    , "with"
+    ];
+
+#[allow(non_upper_case_globals)]
+static OCamlTypes: [&str; 14] =
+    [ "array"
+    , "bool"
+    , "bytes"
+    , "char"
+    , "exn"
+    , "float"
+    , "int"
+    , "int32"
+    , "int64"
+    , "list"
+    , "option"
+    , "ref"
+    , "string"
+    , "unit"
+    ];
+
+// OCaml types are lowercase, so the capitalized words are constructors and modules
+#[allow(non_upper_case_globals)]
+pub static OCamlConfig: ColorsConfig = ColorsConfig {
+    language           : "ocaml",
+    is_keyword         : |word| OCamlKeywords.contains(&word),
+    is_type_name       : |word| OCamlTypes.contains(&word) || word.starts_with(char::is_uppercase),
+    line_comment       : None,
+    block_comment      : Some(("(*", "*)")),
+    symbolic_comments  : false,
+    string_quote       : Some('"'),
+    char_quote         : Some('\''),
+    multi_line_strings : true,
+    word_chars         : "'",
+    bird_tracks        : false,
+    indent             : IndentConfig { tab_width: Some(2), ..PlainIndent },
+};
@@ -0,0 +1,46 @@
+use super::{ haskell, ColorsConfig };
+
+#[allow(non_upper_case_globals)]
+static PureScriptKeywords: [&str; 25] =
+    [ "ado"
+    , "as"
+    , "case"
+    , "class"
+    , "data"
+    , "derive"
+    , "do"
+    , "else"
+    , "forall"
+    , "foreign"
+    , "hiding"
+    , "if"
+    , "import"
+    , "in"
+    , "infix"
+    , "infixl"
+    , "infixr"
+    , "instance"
+    , "let"
+    , "module"
+    , "newtype"
+    , "of"
+    , "then"
+    , "type"
+    , "where"
+    ];
+
+#[allow(non_upper_case_globals)]
+pub static PureScriptConfig: ColorsConfig = ColorsConfig {
+    language           : "purescript",
+    is_keyword         : |word| PureScriptKeywords.contains(&word),
+    is_type_name       : |word| word.starts_with(char::is_uppercase),
+    line_comment       : Some("--"),
+    block_comment      : Some(("{-", "-}")),
+    symbolic_comments  : true,
+    string_quote       : Some('"'),
+    char_quote         : Some('\''),
+    multi_line_strings : false,
+    word_chars         : "'",
+    bird_tracks        : false,
+    indent             : haskell::HaskellIndent,
+};
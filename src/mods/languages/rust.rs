@@ -1,7 +1,7 @@
-use super::ColorsConfig;
+use super::{ ColorsConfig, IndentConfig };
 
 #[allow(non_upper_case_globals)]
-static RustKeywords: [&'static str; 35] =
+static RustKeywords: [&str; 35] =
     [ "as"
     , "break"
     , "const"
@@ -40,7 +40,7 @@ static RustKeywords: [&'static str; 35] =
     ];
 
 #[allow(non_upper_case_globals)]
-static RustTypes: [&'static str; 21] =
+static RustTypes: [&str; 21] =
     [ "bool"
     , "char"
     , "isize"
@@ -66,10 +66,24 @@ static RustTypes: [&'static str; 21] =
 
 #[allow(non_upper_case_globals)]
 pub static RustConfig: ColorsConfig = ColorsConfig {
-    is_keyword      : |word| RustKeywords.contains(&word),
-    is_type_name    : |word| RustTypes.contains(&word),
-    num_color       : "red",
-    type_name_color : "blue",
-    keyword_color   : "green",
-    default_color   : "white",
+    language           : "rust",
+    is_keyword         : |word| RustKeywords.contains(&word),
+    is_type_name       : |word| RustTypes.contains(&word),
+    line_comment       : Some("//"),
+    block_comment      : Some(("/*", "*/")),
+    symbolic_comments  : false,
+    string_quote       : Some('"'),
+    char_quote         : Some('\''),
+    multi_line_strings : true,
+    word_chars         : "",
+    bird_tracks        : false,
+    indent             : RustIndent,
+};
+
+#[allow(non_upper_case_globals)]
+pub static RustIndent: IndentConfig = IndentConfig {
+    opens_block : |code| code.ends_with(['{', '(', '[']),
+    closing     : "})]",
+    tab_width   : None,
+    hard_tabs   : false,
 };
@@ -0,0 +1,72 @@
+use super::{ rust, ColorsConfig, IndentConfig };
+
+#[allow(non_upper_case_globals)]
+static ScalaKeywords: [&str; 44] =
+    [ "abstract"
+    , "case"
+    , "catch"
+    , "class"
+    , "def"
+    , "do"
+    , "else"
+    , "enum"
+    , "export"
+    , "extends"
+    , "false"
+    , "final"
+    , "finally"
+    , "for"
+    , "forSome"
+    , "given"
+    , "if"
+    , "implicit"
+    , "import"
+    , "lazy"
+    , "match"
+    , "new"
+    , "null"
+    , "object"
+    , "override"
+    , "package"
+    , "private"
+    , "protected"
+    , "return"
+    , "sealed"
+    , "super"
+    , "then"
+    , "this"
+    , "throw"
+    , "trait"
+    , "true"
+    , "try"
+    , "type"
+    , "using"
+    , "val"
+    , "var"
+    , "while"
+    , "with"
+    , "yield"
+    ];
+
+#[allow(non_upper_case_globals)]
+pub static ScalaConfig: ColorsConfig = ColorsConfig {
+    language           : "scala",
+    is_keyword         : |word| ScalaKeywords.contains(&word),
+    is_type_name       : |word| word.starts_with(char::is_uppercase),
+    line_comment       : Some("//"),
+    block_comment      : Some(("/*", "*/")),
+    symbolic_comments  : false,
+    string_quote       : Some('"'),
+    char_quote         : Some('\''),
+    multi_line_strings : false,
+    word_chars         : "",
+    bird_tracks        : false,
+    indent             : ScalaIndent,
+};
+
+// The braces of Rust, indented by two spaces
+#[allow(non_upper_case_globals)]
+pub static ScalaIndent: IndentConfig = IndentConfig {
+    tab_width : Some(2),
+    ..rust::RustIndent
+};
@@ -0,0 +1,314 @@
+use super::languages::ColorsConfig;
+
+// Chars that make up operators, like -> or >>=
+const SYMBOLS: &str = "!#$%&*+-./:<=>?@\\^|~";
+
+/* What a row ends in the middle of, so that the next one is tokenized from
+ * there. Comments nest, so the depth is kept. */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Context {
+    Code,
+    Comment(usize),
+    String,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Token {
+    Word,
+    Number,
+    Comment,
+    String,
+    Operator,
+    // spaces, brackets and the like
+    Other,
+}
+
+/* Splits a row into tokens, starting in the given context. Each token is
+ * given with the byte index where it ends, and the context at the end of the
+ * row is returned along with them. */
+pub fn tokenize(text: &str, syntax: &ColorsConfig, context: Context)
+               -> (Vec<(Token, usize)>, Context) {
+    let mut lexer = Lexer { text, syntax, pos: 0, tokens: Vec::new() };
+    if syntax.bird_tracks {
+        // the other rows are prose, which leaves the context as it was
+        if !text.starts_with('>') {
+            lexer.pos = text.len();
+            lexer.push(Token::Comment);
+            return (lexer.tokens, context);
+        }
+        lexer.bump();
+        lexer.push(Token::Other);
+    }
+    let mut context = match context {
+        Context::Code           => Context::Code,
+        Context::Comment(depth) => lexer.comment(depth),
+        Context::String         => lexer.string(),
+    };
+    if context == Context::Code {
+        context = lexer.code();
+    }
+    (lexer.tokens, context)
+}
+
+struct Lexer<'a> {
+    text   : &'a str,
+    syntax : &'a ColorsConfig,
+    pos    : usize,
+    tokens : Vec<(Token, usize)>,
+}
+
+impl<'a> Lexer<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos ..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn bump(&mut self) {
+        if let Some(c) = self.peek() {
+            self.pos += c.len_utf8();
+        }
+    }
+
+    fn eat_while<F: Fn(char) -> bool>(&mut self, accept: F) {
+        while self.peek().is_some_and(&accept) {
+            self.bump();
+        }
+    }
+
+    // Ends the current token, merging it with the previous one if they are alike
+    fn push(&mut self, token: Token) {
+        match self.tokens.last_mut() {
+            Some(last) if last.0 == token => last.1 = self.pos,
+            _                             => self.tokens.push((token, self.pos)),
+        }
+    }
+
+    fn code(&mut self) -> Context {
+        while let Some(c) = self.peek() {
+            if self.at_line_comment(true) {
+                self.pos = self.text.len();
+                self.push(Token::Comment);
+            } else if let Some(open) = self.at_block_comment() {
+                self.pos += open.len();
+                let context = self.comment(1);
+                if context != Context::Code {
+                    return context;
+                }
+            } else if Some(c) == self.syntax.string_quote {
+                self.bump();
+                let context = self.string();
+                if context != Context::Code {
+                    return context;
+                }
+            } else if Some(c) == self.syntax.char_quote && self.char_literal(c) {
+                self.push(Token::String);
+            } else if c.is_ascii_digit() {
+                self.number();
+            } else if c.is_alphabetic() || c == '_' {
+                let extra = self.syntax.word_chars;
+                self.eat_while(|c| c.is_alphanumeric() || c == '_' || extra.contains(c));
+                self.push(Token::Word);
+            } else if SYMBOLS.contains(c) {
+                self.bump();
+                while self.peek().is_some_and(|c| SYMBOLS.contains(c))
+                      && !self.at_line_comment(false)
+                      && self.at_block_comment().is_none() {
+                    self.bump();
+                }
+                self.push(Token::Operator);
+            } else {
+                self.bump();
+                self.push(Token::Other);
+            }
+        }
+        Context::Code
+    }
+
+    /* Whether a line comment starts here. In languages where the marker can
+     * be part of an operator, like -- in Haskell, the whole operator has to
+     * be made of the chars of the marker. */
+    fn at_line_comment(&self, token_start: bool) -> bool {
+        let rest = self.rest();
+        match self.syntax.line_comment {
+            Some(marker) if rest.starts_with(marker) => {
+                !self.syntax.symbolic_comments
+                    || token_start && rest.chars()
+                                          .take_while(|&c| SYMBOLS.contains(c))
+                                          .all(|c| marker.contains(c))
+            }
+            _ => false,
+        }
+    }
+
+    fn at_block_comment(&self) -> Option<&'static str> {
+        self.syntax.block_comment
+            .map(|(open, _)| open)
+            .filter(|open| self.rest().starts_with(open))
+    }
+
+    // Goes through block comments, given how many of them are open
+    fn comment(&mut self, depth: usize) -> Context {
+        let (open, close) = match self.syntax.block_comment {
+            Some(markers) => markers,
+            None          => return Context::Code,
+        };
+        let mut depth = depth;
+        while depth > 0 {
+            let rest = self.rest();
+            if rest.is_empty() {
+                self.push(Token::Comment);
+                return Context::Comment(depth);
+            } else if rest.starts_with(close) {
+                self.pos += close.len();
+                depth -= 1;
+            } else if rest.starts_with(open) {
+                self.pos += open.len();
+                depth += 1;
+            } else {
+                self.bump();
+            }
+        }
+        self.push(Token::Comment);
+        Context::Code
+    }
+
+    /* Goes through a string, after its opening quote. A string that is not
+     * closed goes on in the next row if the language allows it, or if the
+     * row ends with a backslash. */
+    fn string(&mut self) -> Context {
+        let quote = match self.syntax.string_quote {
+            Some(quote) => quote,
+            None        => return Context::Code,
+        };
+        loop {
+            match self.peek() {
+                None => {
+                    self.push(Token::String);
+                    return if self.syntax.multi_line_strings { Context::String }
+                           else { Context::Code };
+                }
+                Some('\\') => {
+                    self.bump();
+                    if self.rest().is_empty() {
+                        self.push(Token::String);
+                        return Context::String;
+                    }
+                    self.bump();
+                }
+                Some(c) if c == quote => {
+                    self.bump();
+                    self.push(Token::String);
+                    return Context::Code;
+                }
+                Some(_) => self.bump(),
+            }
+        }
+    }
+
+    /* Goes through a char literal like 'a' or '\n', if there is one here.
+     * Otherwise the quote is something else, like the start of a lifetime
+     * in Rust. */
+    fn char_literal(&mut self, quote: char) -> bool {
+        let body = &self.rest()[quote.len_utf8() ..];
+        let mut chars = body.char_indices();
+        let closing = match chars.next() {
+            Some((_, '\\')) => {
+                chars.next();
+                chars.take(10).find(|&(_, c)| c == quote)
+            }
+            Some((_, c)) if c != quote => chars.next().filter(|&(_, c)| c == quote),
+            _ => None,
+        };
+        match closing {
+            Some((index, _)) => {
+                self.pos += 2 * quote.len_utf8() + index;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Decimal, hexadecimal, octal and binary numbers, with '_' and suffixes like u8
+    fn number(&mut self) {
+        let rest = self.rest();
+        let radix = rest.chars().nth(1).filter(|_| rest.starts_with('0'));
+        if radix.is_some_and(|c| "xXoObB".contains(c)) {
+            self.pos += 2;
+            self.eat_while(|c| c.is_ascii_hexdigit() || c == '_');
+        } else {
+            self.eat_while(|c| c.is_ascii_digit() || c == '_');
+            let mut fraction = self.rest().chars();
+            if fraction.next() == Some('.') && fraction.next().is_some_and(|c| c.is_ascii_digit()) {
+                self.bump();
+                self.eat_while(|c| c.is_ascii_digit() || c == '_');
+            }
+            let rest = self.rest();
+            let exponent = rest.strip_prefix(['e', 'E'])
+                               .map(|digits| digits.strip_prefix(['+', '-']).unwrap_or(digits))
+                               .filter(|digits| digits.starts_with(|c: char| c.is_ascii_digit()));
+            if let Some(digits) = exponent {
+                self.pos += rest.len() - digits.len();
+                self.eat_while(|c| c.is_ascii_digit() || c == '_');
+            }
+        }
+        self.eat_while(|c| c.is_alphanumeric() || c == '_');
+        self.push(Token::Number);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ tokenize, Context, Token };
+    use crate::mods::languages;
+
+    fn tokens(language: &str, text: &str, context: Context) -> (Vec<(Token, usize)>, Context) {
+        tokenize(text, &languages::by_name(language).unwrap(), context)
+    }
+
+    #[test]
+    fn nested_comments_go_on_over_the_rows() {
+        assert_eq!(tokens("haskell", "x {- a {- b", Context::Code),
+                   (vec![(Token::Word, 1), (Token::Other, 2), (Token::Comment, 11)],
+                    Context::Comment(2)));
+        assert_eq!(tokens("haskell", "c -} d", Context::Comment(2)),
+                   (vec![(Token::Comment, 6)], Context::Comment(1)));
+        assert_eq!(tokens("haskell", "-} y", Context::Comment(1)),
+                   (vec![(Token::Comment, 2), (Token::Other, 3), (Token::Word, 4)], Context::Code));
+    }
+
+    #[test]
+    fn comments_opened_in_a_string_are_not_comments() {
+        assert_eq!(tokens("rust", "\"/*\" x", Context::Code),
+                   (vec![(Token::String, 4), (Token::Other, 5), (Token::Word, 6)], Context::Code));
+    }
+
+    #[test]
+    fn strings_go_on_in_the_next_row_where_the_language_allows_it() {
+        assert_eq!(tokens("clojure", "(def s \"abc", Context::Code).1, Context::String);
+        assert_eq!(tokens("clojure", "def\" x", Context::String),
+                   (vec![(Token::String, 4), (Token::Other, 5), (Token::Word, 6)], Context::Code));
+        // elsewhere an unclosed string ends with its row
+        assert_eq!(tokens("haskell", "s = \"abc", Context::Code),
+                   (vec![(Token::Word, 1), (Token::Other, 2), (Token::Operator, 3),
+                         (Token::Other, 4), (Token::String, 8)],
+                    Context::Code));
+    }
+
+    #[test]
+    fn a_backslash_at_the_end_continues_the_string() {
+        assert_eq!(tokens("haskell", "s = \"ab\\", Context::Code).1, Context::String);
+        assert_eq!(tokens("haskell", "\\cd\" x", Context::String),
+                   (vec![(Token::String, 4), (Token::Other, 5), (Token::Word, 6)], Context::Code));
+    }
+
+    #[test]
+    fn escaped_quotes_do_not_close_strings_or_chars() {
+        assert_eq!(tokens("rust", "\"a\\\"b\" c", Context::Code),
+                   (vec![(Token::String, 6), (Token::Other, 7), (Token::Word, 8)], Context::Code));
+        assert_eq!(tokens("rust", "'\\'' x", Context::Code),
+                   (vec![(Token::String, 4), (Token::Other, 5), (Token::Word, 6)], Context::Code));
+    }
+}
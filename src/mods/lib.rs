@@ -1,50 +1,404 @@
-use super::languages::{ ColorsConfig,
-                        haskell::HaskellConfig,
-                        rust::RustConfig };
-
-pub fn get_color_config(from: &String) -> ColorsConfig {
-    match get_extension(from) {
-        Some(ext) => match &ext[..] {
-                        "hs" => HaskellConfig,
-                        "rs" => RustConfig,
-                         _   => Default::default(),
-                     },
-        None      => Default::default(),
-    }
-}
-
-pub fn get_extension(file_name: &String) -> Option<String> {
-    let words = file_name.split(".")
-                         .map(|word| word.to_string())
-                         .collect::<Vec<String>>();
-    if words.len() < 2 {
-        None
+use std::path::Path;
+use std::sync::LazyLock;
+
+use regex::Regex;
+use unicode_segmentation::{ GraphemeCursor, UnicodeSegmentation };
+use unicode_width::UnicodeWidthStr;
+
+use super::languages::{ self, ColorsConfig };
+
+/* Finds the language of a file from its first and last rows: a shebang
+ * line, then an Emacs or Vim modeline, then the name of well-known files
+ * like Makefile, and at last the extension. */
+pub fn detect_language(file_name: &str, head: &[String], tail: &[String]) -> ColorsConfig {
+    let name = head.first().and_then(|line| from_shebang(line))
+                   .or_else(|| head.iter().take(2).find_map(|line| from_emacs_modeline(line)))
+                   .or_else(|| head.iter().take(5).chain(tail).find_map(|line| from_vim_modeline(line)))
+                   .or_else(|| from_file_name(file_name));
+    name.and_then(|name| languages::by_name(&name)).unwrap_or_default()
+}
+
+fn from_extension(ext: &str) -> Option<&'static str> {
+    let name = match ext {
+        "hs"                    => "haskell",
+        "lhs"                   => "literate_haskell",
+        "rs"                    => "rust",
+        "ml" | "mli"            => "ocaml",
+        "elm"                   => "elm",
+        "fs" | "fsi" | "fsx"    => "fsharp",
+        "scala" | "sc" | "sbt"  => "scala",
+        "lean"                  => "lean",
+        "agda"                  => "agda",
+        "idr"                   => "idris",
+        "purs"                  => "purescript",
+        "clj" | "cljs" | "cljc" => "clojure",
+        "lisp" | "scm" | "rkt"  => "lisp",
+        "mk"                    => "make",
+        "yaml" | "yml"          => "yaml",
+        "toml"                  => "toml",
+        "cabal"                 => "cabal",
+        _                       => return None,
+    };
+    Some(name)
+}
+
+fn from_file_name(file_name: &str) -> Option<String> {
+    let base = Path::new(file_name).file_name()?.to_string_lossy();
+    let name = match &base[..] {
+        "Makefile" | "GNUmakefile" | "makefile"         => "make",
+        "dune" | "dune-project" | "dune-workspace"
+        | ".emacs"                                      => "lisp",
+        ".ghci"                                         => "haskell",
+        ".ocamlinit"                                    => "ocaml",
+        "cabal.project"                                 => "cabal",
+        "Cargo.lock"                                    => "toml",
+        _                                               => from_extension(&get_extension(file_name)?)?,
+    };
+    Some(name.to_string())
+}
+
+/* The language of a script like "#!/usr/bin/env runghc", from the name of
+ * its interpreter. With env the options and variables before it are
+ * skipped. */
+fn from_shebang(line: &str) -> Option<String> {
+    let mut words = line.strip_prefix("#!")?.split_whitespace();
+    let mut program = Path::new(words.next()?).file_name()?.to_string_lossy().into_owned();
+    if program == "env" {
+        program = words.find(|word| !word.starts_with('-') && !word.contains('='))?.to_string();
+    }
+    // versions, like in python3 or idris2
+    let program = program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    let name = match program {
+        "runghc" | "runhaskell" | "stack" | "cabal" | "ghc" => "haskell",
+        "ocaml" | "utop"                                    => "ocaml",
+        "scala" | "amm" | "scala-cli"                       => "scala",
+        "racket" | "guile" | "sbcl" | "clisp" | "scheme"    => "lisp",
+        "clojure" | "clj" | "bb"                            => "clojure",
+        "rust-script" | "run-cargo-script"                  => "rust",
+        "make"                                              => "make",
+        _                                                   => return language_alias(program),
+    };
+    Some(name.to_string())
+}
+
+// A modeline like "-*- mode: haskell -*-", or only "-*- haskell -*-"
+fn from_emacs_modeline(line: &str) -> Option<String> {
+    let (_, rest) = line.split_once("-*-")?;
+    let (vars, _) = rest.split_once("-*-")?;
+    let mode = if vars.contains(':') {
+                   vars.split(';')
+                       .filter_map(|var| var.split_once(':'))
+                       .find(|(key, _)| key.trim().eq_ignore_ascii_case("mode"))?
+                       .1
+               } else {
+                   vars
+               };
+    language_alias(&mode.trim().to_lowercase())
+}
+
+// A modeline like "vim: set ft=haskell:" or "vi: syntax=lisp"
+fn from_vim_modeline(line: &str) -> Option<String> {
+    static MODELINE: LazyLock<Regex> = LazyLock::new(|| {
+        Regex::new(r"(?:^|\s)(?:vim?|ex):.*?\b(?:ft|filetype|syntax)=([\w+-]+)").unwrap()
+    });
+    language_alias(&MODELINE.captures(line)?[1].to_lowercase())
+}
+
+// Names given to the languages by editors and interpreters, or by LANGUAGE_NAMES
+fn language_alias(name: &str) -> Option<String> {
+    let name = name.strip_suffix("-mode").unwrap_or(name);
+    let name = match name {
+        "hs"                                   => "haskell",
+        "lhaskell" | "haskell-literate"        => "literate_haskell",
+        "caml" | "tuareg"                      => "ocaml",
+        "fs"                                   => "fsharp",
+        "purs"                                 => "purescript",
+        "clj"                                  => "clojure",
+        "scheme" | "racket" | "emacs-lisp"
+        | "elisp" | "common-lisp" | "lisp"     => "lisp",
+        "mk" | "makefile" | "gmake"            => "make",
+        "yml"                                  => "yaml",
+        "rs"                                   => "rust",
+        _                                      => name,
+    };
+    languages::by_name(name).map(|_| name.to_string())
+}
+
+pub fn get_extension(file_name: &str) -> Option<String> {
+    Path::new(file_name).extension().map(|ext| ext.to_string_lossy().into_owned())
+}
+
+/* Whether ch is not part of a word, in a language whose words can also have
+ * the word_chars of its ColorsConfig, like ' in Haskell. */
+pub fn is_separator(ch: char, word_chars: &str) -> bool {
+    !(ch.is_alphanumeric() || ch == '_' || word_chars.contains(ch))
+}
+
+// Blanks, words, and runs of the other separators, like operators
+#[derive(PartialEq, Eq)]
+pub enum WordKind {
+    Blank,
+    Word,
+    Symbol,
+}
+
+pub fn word_kind(grapheme: &str, word_chars: &str) -> WordKind {
+    match grapheme.chars().next() {
+        Some(ch) if ch.is_whitespace()            => WordKind::Blank,
+        Some(ch) if !is_separator(ch, word_chars) => WordKind::Word,
+        _                                         => WordKind::Symbol,
+    }
+}
+
+/* The col where the word before col starts, after the blanks before col.
+ * A run of separators, like an operator, counts as a word. */
+pub fn word_start(text: &str, col: usize, word_chars: &str) -> usize {
+    let kinds: Vec<WordKind> = text.graphemes(true)
+                                   .take(col)
+                                   .map(|grapheme| word_kind(grapheme, word_chars))
+                                   .collect();
+    let mut start = kinds.len();
+    while start > 0 && kinds[start - 1] == WordKind::Blank {
+        start -= 1;
+    }
+    if start > 0 {
+        let kind = &kinds[start - 1];
+        while start > 0 && kinds[start - 1] == *kind {
+            start -= 1;
+        }
+    }
+    start
+}
+
+// The col where the word after col ends, after the blanks after col
+pub fn word_end(text: &str, col: usize, word_chars: &str) -> usize {
+    let kinds: Vec<WordKind> = text.graphemes(true)
+                                   .skip(col)
+                                   .map(|grapheme| word_kind(grapheme, word_chars))
+                                   .collect();
+    let blanks = kinds.iter().take_while(|&kind| *kind == WordKind::Blank).count();
+    let word = kinds.get(blanks).map_or(0, |kind| {
+        kinds[blanks ..].iter().take_while(|&other| other == kind).count()
+    });
+    col + blanks + word
+}
+
+/* Columns of the editor are counted in grapheme clusters, so that a letter
+ * followed by combining marks is a single column. */
+pub fn grapheme_count(text: &str) -> usize {
+    text.graphemes(true).count()
+}
+
+// Whether a byte index of text is between two graphemes, or at one of its ends
+pub fn is_grapheme_boundary(text: &str, index: usize) -> bool {
+    GraphemeCursor::new(index, text.len(), true).is_boundary(text, 0).unwrap_or(false)
+}
+
+// Byte offset of the col-th grapheme of text (or its length if there are fewer)
+pub fn byte_index(text: &str, col: usize) -> usize {
+    text.grapheme_indices(true)
+        .nth(col)
+        .map_or(text.len(), |(index, _)| index)
+}
+
+// Number of terminal cells used to display text (wide CJK chars use two)
+pub fn display_width(text: &str) -> usize {
+    UnicodeWidthStr::width(text)
+}
+
+// Cells used by a grapheme shown from the given cell: tabs go up to the next tab stop
+pub fn grapheme_width(grapheme: &str, cell: usize, tab_width: usize) -> usize {
+    if grapheme == "\t" {
+        tab_width - cell % tab_width
     } else {
-        words.last().cloned()
+        display_width(grapheme)
+    }
+}
+
+/* Number of terminal cells before the col-th grapheme of text. The cursor
+ * is kept as a col in the text, and this is where it is on the screen. */
+pub fn display_col(text: &str, col: usize, tab_width: usize) -> usize {
+    text.graphemes(true)
+        .take(col)
+        .fold(0, |cell, grapheme| cell + grapheme_width(grapheme, cell, tab_width))
+}
+
+// The col of the grapheme shown at a cell, or the end of the text if it is shorter
+pub fn col_at_cell(text: &str, cell: usize, tab_width: usize) -> usize {
+    let mut start = 0;
+    for (col, grapheme) in text.graphemes(true).enumerate() {
+        start += grapheme_width(grapheme, start, tab_width);
+        if start > cell {
+            return col;
+        }
     }
+    grapheme_count(text)
 }
 
-pub fn is_separator(ch: char) -> bool {
-    ch == ' ' || ch == '.' || ch == '(' || ch == ')'
+// Longest prefix of text (in whole graphemes) that fits in the given cells
+pub fn truncate_to_width(text: &str, width: usize) -> String {
+    let mut used = 0;
+    text.graphemes(true)
+        .take_while(|grapheme| { used += display_width(grapheme); used <= width })
+        .collect()
 }
 
-pub fn words_and_separators(text: &str) -> Vec<(String, String)> {
-    let mut words_and_separators = Vec::new();
-    let mut chunk = String::new();
-    let mut separators = String::new();
-    for ch in text.chars() {
-        if is_separator(ch) {
-            separators.push(ch);
-        } else {
-            if separators.is_empty() {
-                chunk.push(ch);
+/* Lines of a diff between two texts, starting with '-' for the lines only in
+ * old, '+' for the ones only in new and ' ' for the ones in both. Only a few
+ * of the unchanged lines around the changes are kept at the start and end. */
+pub fn diff_lines(old: &str, new: &str) -> Vec<String> {
+    const CONTEXT: usize = 2;
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix ..].iter().rev()
+                               .zip(new[prefix ..].iter().rev())
+                               .take_while(|(a, b)| a == b)
+                               .count();
+    let a = &old[prefix .. old.len() - suffix];
+    let b = &new[prefix .. new.len() - suffix];
+    let line = |sign: &str, text: &str| format!("{} {}", sign, text);
+
+    let mut lines: Vec<String> =
+        old[prefix.saturating_sub(CONTEXT) .. prefix].iter().map(|text| line(" ", text)).collect();
+    if a.len().saturating_mul(b.len()) > 4_000_000 {
+        // too big to be compared, so all of it is shown as replaced
+        lines.extend(a.iter().map(|text| line("-", text)));
+        lines.extend(b.iter().map(|text| line("+", text)));
+    } else {
+        // length of the longest common subsequence of a[i ..] and b[j ..]
+        let mut common = vec![vec![0; b.len() + 1]; a.len() + 1];
+        for i in (0 .. a.len()).rev() {
+            for j in (0 .. b.len()).rev() {
+                common[i][j] =
+                    if a[i] == b[j] {
+                        common[i + 1][j + 1] + 1
+                    } else {
+                        std::cmp::max(common[i + 1][j], common[i][j + 1])
+                    };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() || j < b.len() {
+            if i < a.len() && j < b.len() && a[i] == b[j] {
+                lines.push(line(" ", a[i]));
+                i += 1;
+                j += 1;
+            } else if j == b.len() || (i < a.len() && common[i + 1][j] >= common[i][j + 1]) {
+                lines.push(line("-", a[i]));
+                i += 1;
             } else {
-                words_and_separators.push((chunk.clone(), separators.clone()));
-                chunk = String::from(ch);
-                separators.clear();
+                lines.push(line("+", b[j]));
+                j += 1;
             }
         }
     }
-    words_and_separators.push((chunk.clone(), separators.clone()));
-    words_and_separators
+    lines.extend(old[old.len() - suffix ..].iter().take(CONTEXT).map(|text| line(" ", text)));
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn language(file_name: &str, head: &[&str], tail: &[&str]) -> &'static str {
+        let rows = |lines: &[&str]| lines.iter().map(|line| line.to_string()).collect::<Vec<_>>();
+        detect_language(file_name, &rows(head), &rows(tail)).language
+    }
+
+    #[test]
+    fn shebangs_give_the_language_of_their_interpreter() {
+        assert_eq!(language("script", &["#!/usr/bin/env runhaskell"], &[]), "haskell");
+        assert_eq!(language("script", &["#!/usr/bin/env -S LC_ALL=C ocaml"], &[]), "ocaml");
+        assert_eq!(language("script", &["#!/usr/local/bin/racket"], &[]), "lisp");
+        assert_eq!(language("script", &["#!/usr/bin/env python3"], &[]), "text");
+    }
+
+    #[test]
+    fn emacs_modelines_are_read_from_the_first_two_rows() {
+        assert_eq!(language("notes", &["-- -*- mode: haskell; coding: utf-8 -*-"], &[]), "haskell");
+        assert_eq!(language("notes", &["#!/bin/sh", "# -*- Tuareg -*-"], &[]), "ocaml");
+        assert_eq!(language("notes", &["", "", "-*- mode: haskell -*-"], &[]), "text");
+    }
+
+    #[test]
+    fn vim_modelines_are_read_from_the_first_and_last_rows() {
+        assert_eq!(language("notes", &["# vim: set ft=yaml:"], &[]), "yaml");
+        assert_eq!(language("notes", &["x"], &["-- vi: filetype=hs"]), "haskell");
+        assert_eq!(language("notes", &["vim: ft=haskell"], &[]), "haskell");
+        // a word ending in vim isn't a modeline
+        assert_eq!(language("notes", &["nvim: ft=haskell"], &[]), "text");
+    }
+
+    #[test]
+    fn shebangs_come_before_modelines_and_modelines_before_the_name() {
+        assert_eq!(language("a.rs", &["#!/usr/bin/env runghc", "-- -*- mode: ocaml -*-"], &[]),
+                   "haskell");
+        assert_eq!(language("a.rs", &["-*- mode: ocaml -*-"], &["// vim: ft=haskell"]), "ocaml");
+        assert_eq!(language("a.rs", &["fn main() {}"], &["// vim: ft=haskell"]), "haskell");
+    }
+
+    #[test]
+    fn file_names_come_before_extensions() {
+        assert_eq!(language("project/Makefile", &[], &[]), "make");
+        assert_eq!(language("Cargo.lock", &[], &[]), "toml");
+        assert_eq!(language("lib/Parser.lhs", &[], &[]), "literate_haskell");
+    }
+
+    #[test]
+    fn unknown_files_are_text() {
+        assert_eq!(language("picture.xyz", &[], &[]), "text");
+        assert_eq!(language("README", &[], &[]), "text");
+        assert_eq!(language("a.rs", &["# vim: ft=cobol"], &[]), "rust");
+    }
+
+    #[test]
+    fn word_start_skips_the_blanks_then_one_word() {
+        assert_eq!(word_start("foo bar", 7, ""), 4);
+        assert_eq!(word_start("foo bar", 5, ""), 4);
+        assert_eq!(word_start("foo bar", 4, ""), 0);
+        assert_eq!(word_start("foo   ", 6, ""), 0);
+    }
+
+    #[test]
+    fn word_end_skips_the_blanks_then_one_word() {
+        assert_eq!(word_end("foo bar", 0, ""), 3);
+        assert_eq!(word_end("foo bar", 1, ""), 3);
+        assert_eq!(word_end("foo bar", 3, ""), 7);
+        assert_eq!(word_end("   foo", 0, ""), 6);
+    }
+
+    #[test]
+    fn runs_of_punctuation_are_words_of_their_own() {
+        assert_eq!(word_start("x >>= y", 5, ""), 2);
+        assert_eq!(word_end("x >>= y", 1, ""), 5);
+        assert_eq!(word_start("a.b", 3, ""), 2);
+        assert_eq!(word_start("a.b", 2, ""), 1);
+        assert_eq!(word_end("f(x)", 1, ""), 2);
+    }
+
+    #[test]
+    fn words_stop_at_the_ends_of_the_row() {
+        assert_eq!(word_start("", 0, ""), 0);
+        assert_eq!(word_start("  ", 2, ""), 0);
+        assert_eq!(word_end("foo", 3, ""), 3);
+        assert_eq!(word_end("foo  ", 3, ""), 5);
+    }
+
+    #[test]
+    fn word_chars_of_the_language_are_part_of_words() {
+        assert_eq!(word_end("foo' x", 0, ""), 3);
+        assert_eq!(word_end("foo' x", 0, "'"), 4);
+        assert_eq!(word_start("x foo'", 6, "'"), 2);
+    }
+
+    #[test]
+    fn words_are_counted_in_graphemes() {
+        // e followed by a combining accent is a single col
+        let text = "nai\u{308}ve e\u{301}te\u{301}";
+        assert_eq!(word_end(text, 0, ""), 5);
+        assert_eq!(word_start(text, grapheme_count(text), ""), 6);
+        assert_eq!(word_end("日本 語", 0, ""), 2);
+        assert_eq!(word_start("日本 語", 4, ""), 3);
+    }
 }
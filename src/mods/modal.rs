@@ -0,0 +1,558 @@
+use std::cmp;
+
+use termion::event::Key;
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::data::Data;
+use super::interface;
+use super::lib::{ self, WordKind };
+use super::state::State;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum Mode {
+    #[default]
+    Normal,
+    Insert,
+    Visual,
+}
+
+impl Mode {
+    // Shown in the status line of the active window
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mode::Normal => "NORMAL",
+            Mode::Insert => "INSERT",
+            Mode::Visual => "VISUAL",
+        }
+    }
+}
+
+/* An optional vi-like layer over the default key bindings. In Normal and
+ * Visual modes keys are gathered until they make a command, like "3dw" or
+ * "yy". In Insert mode they keep their default meaning until Escape. The
+ * keys of the last change are kept so that "." can play them again. */
+#[derive(Default)]
+pub struct Modal {
+    pub enabled : bool,
+    pub mode    : Mode,
+    pending     : Vec<Key>,
+    change      : Option<Vec<Key>>,
+    last_change : Vec<Key>,
+    replaying   : bool,
+    anchor      : (usize, usize),
+}
+
+impl Modal {
+    pub fn new() -> Modal {
+        Default::default()
+    }
+}
+
+enum Parsed<T> {
+    Incomplete,
+    Invalid,
+    Done(T),
+}
+
+impl<T> Parsed<T> {
+    fn map<U, F: FnOnce(T) -> U>(self, f: F) -> Parsed<U> {
+        match self {
+            Parsed::Incomplete => Parsed::Incomplete,
+            Parsed::Invalid    => Parsed::Invalid,
+            Parsed::Done(done) => Parsed::Done(f(done)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    WordStart,
+    WordBack,
+    WordEnd,
+    RowStart,
+    RowEnd,
+    FirstRow,
+    LastRow,
+}
+
+enum Command {
+    Move(Motion, Option<usize>),
+    // an operator without a motion was typed twice, and acts on whole rows
+    Operate(char, Option<usize>, Option<Motion>),
+    Simple(Key, usize),
+}
+
+impl Command {
+    // Whether "." should repeat the command
+    fn is_change(&self) -> bool {
+        match self {
+            Command::Operate(op, _, _)          => *op != 'y',
+            Command::Simple(Key::Char(c), _)    => "xiaIAoOpPDC".contains(*c),
+            _                                   => false,
+        }
+    }
+}
+
+impl Motion {
+    fn parse(keys: &[Key]) -> Parsed<Motion> {
+        let motion = match keys {
+            [Key::Char('h')] | [Key::Left] | [Key::Backspace] => Motion::Left,
+            [Key::Char('l')] | [Key::Right] | [Key::Char(' ')] => Motion::Right,
+            [Key::Char('k')] | [Key::Up]                       => Motion::Up,
+            [Key::Char('j')] | [Key::Down]                     => Motion::Down,
+            [Key::Char('w')]                                   => Motion::WordStart,
+            [Key::Char('b')]                                   => Motion::WordBack,
+            [Key::Char('e')]                                   => Motion::WordEnd,
+            [Key::Char('0')]                                   => Motion::RowStart,
+            [Key::Char('$')]                                   => Motion::RowEnd,
+            [Key::Char('g'), Key::Char('g')]                   => Motion::FirstRow,
+            [Key::Char('G')]                                   => Motion::LastRow,
+            [Key::Char('g')]                                   => return Parsed::Incomplete,
+            _                                                  => return Parsed::Invalid,
+        };
+        Parsed::Done(motion)
+    }
+
+    // With an operator, these motions act on all the rows they go through
+    fn linewise(self) -> bool {
+        matches!(self, Motion::Up | Motion::Down | Motion::FirstRow | Motion::LastRow)
+    }
+
+    /* Where the motion, repeated count times, takes the cursor. The column may
+     * be the end of the row, which Normal mode moves back to the last grapheme.
+     * For gg and G the count is a row number instead. */
+    fn target(self, data: &Data, word_chars: &str, (row, col): (usize, usize), count: Option<usize>)
+              -> (usize, usize) {
+        let times = count.unwrap_or(1);
+        let last_row = data.len() - 1;
+        let numbered_row = |default| count.map_or(default, |n| cmp::min(n.max(1) - 1, last_row));
+        match self {
+            Motion::Left      => (row, col.saturating_sub(times)),
+            Motion::Right     => (row, cmp::min(col.saturating_add(times), data.row_length(row))),
+            Motion::Up        => (row.saturating_sub(times), col),
+            Motion::Down      => (cmp::min(row.saturating_add(times), last_row), col),
+            Motion::WordStart => (0 .. times).fold((row, col), |pos, _| next_word(data, word_chars, pos)),
+            Motion::WordBack  => (0 .. times).fold((row, col), |pos, _| previous_word(data, word_chars, pos)),
+            Motion::WordEnd   => (0 .. times).fold((row, col), |pos, _| word_end(data, word_chars, pos, true)),
+            Motion::RowStart  => (row, 0),
+            Motion::RowEnd    => {
+                let row = cmp::min(row.saturating_add(times - 1), last_row);
+                (row, data.row_length(row))
+            }
+            Motion::FirstRow  => { let row = numbered_row(0); (row, first_non_blank(data, row)) }
+            Motion::LastRow   => { let row = numbered_row(last_row); (row, first_non_blank(data, row)) }
+        }
+    }
+}
+
+// Reads the count in front of a command. It can't start with 0, which is a motion.
+fn take_count(keys: &[Key]) -> (Option<usize>, &[Key]) {
+    let mut count = None;
+    let mut used = 0;
+    for key in keys {
+        match key {
+            Key::Char(c @ '0' ..= '9') if count.is_some() || *c != '0' => {
+                let digit = c.to_digit(10).unwrap() as usize;
+                count = Some(count.unwrap_or(0usize).saturating_mul(10).saturating_add(digit));
+                used += 1;
+            }
+            _ => break,
+        }
+    }
+    (count, &keys[used ..])
+}
+
+fn parse(keys: &[Key]) -> Parsed<Command> {
+    let (count, rest) = take_count(keys);
+    match rest {
+        [] => Parsed::Incomplete,
+        [Key::Char(op @ ('d' | 'c' | 'y')), rest @ ..] => {
+            let (motion_count, rest) = take_count(rest);
+            // "2d3w" deletes six words
+            let count = match (count, motion_count) {
+                (None, None) => None,
+                _            => Some(count.unwrap_or(1).saturating_mul(motion_count.unwrap_or(1))),
+            };
+            match rest {
+                []                            => Parsed::Incomplete,
+                [Key::Char(c)] if c == op     => Parsed::Done(Command::Operate(*op, count, None)),
+                _ => Motion::parse(rest).map(|motion| Command::Operate(*op, count, Some(motion))),
+            }
+        }
+        [key] if matches!(key, Key::Ctrl('r'))
+                 || matches!(key, Key::Char(c) if "xiaIAoOpPDCuv.".contains(*c)) =>
+            Parsed::Done(Command::Simple(*key, count.unwrap_or(1))),
+        _ => Motion::parse(rest).map(|motion| Command::Move(motion, count)),
+    }
+}
+
+/* Handles the key when the modal layer is on and the key means something in
+ * the current mode. Returns false for the keys left to the default bindings. */
+pub fn interpret_key(key: Key, state: &mut State) -> bool {
+    if !state.modal().enabled {
+        return false;
+    }
+    match state.modal().mode {
+        Mode::Insert => insert_key(key, state),
+        Mode::Normal | Mode::Visual => match key {
+            Key::Char(_) | Key::Esc | Key::Ctrl('r') | Key::Backspace |
+            Key::Left | Key::Right | Key::Up | Key::Down => {
+                command_key(key, state);
+                true
+            }
+            _ => false,
+        },
+    }
+}
+
+fn insert_key(key: Key, state: &mut State) -> bool {
+    let modal = state.modal();
+    if key == Key::Esc {
+        if let Some(mut change) = modal.change.take() {
+            change.push(key);
+            modal.last_change = change;
+        }
+        state.set_mode(Mode::Normal);
+        state.move_cursor(0, -1);
+        return true;
+    }
+    if let (Some(change), Key::Char(_) | Key::Backspace) = (&mut modal.change, key) {
+        change.push(key);
+    }
+    false
+}
+
+fn command_key(key: Key, state: &mut State) {
+    let modal = state.modal();
+    if key == Key::Esc {
+        modal.pending.clear();
+        if modal.mode == Mode::Visual {
+            leave_visual(state);
+        }
+        return;
+    }
+    modal.pending.push(key);
+    if modal.mode == Mode::Visual {
+        if let [Key::Char(op @ ('d' | 'x' | 'c' | 'y'))] = modal.pending[..] {
+            modal.pending.clear();
+            operate_visual(op, state);
+            return;
+        }
+    }
+    let command = match parse(&modal.pending) {
+        Parsed::Incomplete    => return,
+        Parsed::Invalid       => { modal.pending.clear(); return; }
+        Parsed::Done(command) => command,
+    };
+    let keys = std::mem::take(&mut modal.pending);
+
+    if modal.mode == Mode::Visual {
+        run_visual(command, state);
+    } else {
+        if command.is_change() && !modal.replaying {
+            modal.change = Some(keys);
+        }
+        run(command, state);
+        // changes that go on in Insert mode are kept when it is left
+        let modal = state.modal();
+        if modal.mode == Mode::Normal {
+            if let Some(change) = modal.change.take() {
+                modal.last_change = change;
+            }
+        }
+    }
+    fix_cursor(state);
+}
+
+fn run(command: Command, state: &mut State) {
+    let (row, col) = state.cursor();
+    let len = state.data().row_length(row);
+    match command {
+        Command::Move(motion, count) => {
+            let (row, col) = motion.target(state.data(), state.word_chars(), (row, col), count);
+            state.go_to(row, col);
+        }
+        Command::Operate(op, count, motion) => operate(op, count, motion, state),
+        Command::Simple(key, count) => match key {
+            Key::Char('x') if len > 0 => {
+                let end = cmp::min(col + count, len);
+                let removed = state.remove_range((row, col), (row, end));
+                yank(removed, false, state);
+            }
+            Key::Char('i') => state.set_mode(Mode::Insert),
+            Key::Char('a') => { state.go_to(row, cmp::min(col + 1, len)); state.set_mode(Mode::Insert); }
+            Key::Char('I') => {
+                let first = first_non_blank(state.data(), row);
+                state.go_to(row, first);
+                state.set_mode(Mode::Insert);
+            }
+            Key::Char('A') => { state.go_to(row, len); state.set_mode(Mode::Insert); }
+            Key::Char('o') => { state.insert_at(row, len, "\n"); state.set_mode(Mode::Insert); }
+            Key::Char('O') => {
+                state.insert_at(row, 0, "\n");
+                state.go_to(row, 0);
+                state.set_mode(Mode::Insert);
+            }
+            Key::Char('p') => put(true, count, state),
+            Key::Char('P') => put(false, count, state),
+            Key::Char('D') => operate('d', Some(count), Some(Motion::RowEnd), state),
+            Key::Char('C') => operate('c', Some(count), Some(Motion::RowEnd), state),
+            Key::Char('u') => (0 .. count).for_each(|_| state.undo()),
+            Key::Ctrl('r') => (0 .. count).for_each(|_| state.redo()),
+            Key::Char('v') => {
+                state.modal().anchor = (row, col);
+                state.set_mode(Mode::Visual);
+                state.set_selection(Some(((row, col), (row, col))));
+            }
+            Key::Char('.') => repeat(state),
+            _ => (),
+        },
+    }
+}
+
+fn operate(op: char, count: Option<usize>, motion: Option<Motion>, state: &mut State) {
+    let cursor = state.cursor();
+    let data = state.data();
+    let word_chars = state.word_chars();
+    let motion = match motion {
+        Some(motion) => motion,
+        None => {
+            let times = count.unwrap_or(1);
+            let last = cmp::min(cursor.0.saturating_add(times - 1), data.len() - 1);
+            return operate_rows(op, cursor.0, last, state);
+        }
+    };
+    let on_blank = data.get_row(cursor.0)
+                       .graphemes(true)
+                       .nth(cursor.1)
+                       .is_none_or(|grapheme| lib::word_kind(grapheme, word_chars) == WordKind::Blank);
+
+    if op == 'c' && motion == Motion::WordStart && !on_blank {
+        // like in vi, "cw" leaves the blanks after the word alone
+        let end = (1 .. count.unwrap_or(1)).fold(word_end(data, word_chars, cursor, false),
+                                                 |pos, _| word_end(data, word_chars, pos, true));
+        return operate_text(op, cursor, (end.0, end.1 + 1), state);
+    }
+    let target = motion.target(data, word_chars, cursor, count);
+    let (start, mut end) = if cursor <= target { (cursor, target) } else { (target, cursor) };
+    if motion.linewise() {
+        return operate_rows(op, start.0, end.0, state);
+    }
+    if motion == Motion::WordEnd {
+        end.1 += 1;
+    }
+    if motion == Motion::WordStart && end.0 > start.0 && end.1 <= first_non_blank(data, end.0) {
+        // "dw" on the last word of a row stops at the end of the row
+        end = (end.0 - 1, data.row_length(end.0 - 1));
+    }
+    operate_text(op, start, end, state);
+}
+
+// Applies the operator to the text from start up to end (excluded)
+fn operate_text(op: char, start: (usize, usize), end: (usize, usize), state: &mut State) {
+    let end = (end.0, cmp::min(end.1, state.data().row_length(end.0)));
+    if op == 'y' {
+        let text = state.data().text_between(start.0, start.1, end.0, end.1);
+        yank(text, false, state);
+        state.go_to(start.0, start.1);
+        return;
+    }
+    let removed = state.remove_range(start, end);
+    yank(removed, false, state);
+    if op == 'c' {
+        state.set_mode(Mode::Insert);
+    }
+}
+
+// Applies the operator to the rows from first to last, both included
+fn operate_rows(op: char, first: usize, last: usize, state: &mut State) {
+    let data = state.data();
+    let last_len = data.row_length(last);
+    let text = data.text_between(first, 0, last, last_len) + "\n";
+    yank(text, true, state);
+    match op {
+        'y' => state.go_to(first, state.cursor().1),
+        'c' => {
+            state.remove_range((first, 0), (last, last_len));
+            state.set_mode(Mode::Insert);
+        }
+        _ => {
+            let (_, row) = state.remove_rows(first, last);
+            let col = first_non_blank(state.data(), row);
+            state.go_to(row, col);
+        }
+    }
+}
+
+// Deletes and yanks go to the clipboard, shared with Ctrl-x and Ctrl-v
+fn yank(text: String, linewise: bool, state: &mut State) {
+    state.keep_in_clipboard(text, linewise);
+}
+
+// Puts the text last cut, copied, deleted or yanked after or before the cursor
+fn put(after: bool, count: usize, state: &mut State) {
+    let (text, linewise) = match state.clipboard_text() {
+        Some((text, linewise)) => (text.repeat(count), linewise),
+        None                   => return,
+    };
+    let (row, col) = state.cursor();
+    let len = state.data().row_length(row);
+    if !linewise {
+        let at = if after { cmp::min(col + 1, len) } else { col };
+        let (end_row, end_col) = state.insert_at(row, at, &text);
+        state.go_to(end_row, end_col.saturating_sub(1));
+        return;
+    }
+    let row =
+        if after && row + 1 == state.data().len() {
+            // no row follows the last one, so the rows go after its end
+            state.insert_at(row, len, &("\n".to_string() + &text[.. text.len() - 1]));
+            row + 1
+        } else {
+            let row = if after { row + 1 } else { row };
+            state.insert_at(row, 0, &text);
+            row
+        };
+    let col = first_non_blank(state.data(), row);
+    state.go_to(row, col);
+}
+
+// Plays the keys of the last change again
+fn repeat(state: &mut State) {
+    let keys = state.modal().last_change.clone();
+    state.modal().replaying = true;
+    for key in keys {
+        interface::interpret_key(key, state);
+    }
+    state.modal().replaying = false;
+}
+
+fn run_visual(command: Command, state: &mut State) {
+    match command {
+        Command::Move(motion, count) => {
+            let (row, col) = motion.target(state.data(), state.word_chars(), state.cursor(), count);
+            state.go_to(row, col);
+            fix_cursor(state);
+            let anchor = state.modal().anchor;
+            let cursor = state.cursor();
+            state.set_selection(Some(if anchor <= cursor { (anchor, cursor) } else { (cursor, anchor) }));
+        }
+        Command::Simple(Key::Char('v'), _) => leave_visual(state),
+        _ => (),
+    }
+}
+
+// Applies an operator to the selection, which includes both of its ends
+fn operate_visual(op: char, state: &mut State) {
+    let anchor = state.modal().anchor;
+    let cursor = state.cursor();
+    let (start, end) = if anchor <= cursor { (anchor, cursor) } else { (cursor, anchor) };
+    leave_visual(state);
+    operate_text(if op == 'x' { 'd' } else { op }, start, (end.0, end.1 + 1), state);
+    fix_cursor(state);
+}
+
+fn leave_visual(state: &mut State) {
+    state.set_selection(None);
+    state.set_mode(Mode::Normal);
+}
+
+// Out of Insert mode the cursor stays on a grapheme, not after the last one
+fn fix_cursor(state: &mut State) {
+    if state.modal().mode == Mode::Insert {
+        return;
+    }
+    let (row, col) = state.cursor();
+    let len = state.data().row_length(row);
+    if len > 0 && col >= len {
+        state.go_to(row, len - 1);
+    }
+}
+
+// A word is a run of graphemes of the same kind, other than blanks
+fn kinds(data: &Data, word_chars: &str, row: usize) -> Vec<WordKind> {
+    data.get_row(row).graphemes(true).map(|grapheme| lib::word_kind(grapheme, word_chars)).collect()
+}
+
+fn first_non_blank(data: &Data, row: usize) -> usize {
+    kinds(data, "", row).iter().take_while(|&kind| *kind == WordKind::Blank).count()
+}
+
+// Start of the next word, where an empty row also counts as a word
+fn next_word(data: &Data, word_chars: &str, (mut row, mut col): (usize, usize)) -> (usize, usize) {
+    let mut kinds = kinds(data, word_chars, row);
+    if let Some(current) = kinds.get(col) {
+        while col < kinds.len() && kinds[col] == *current {
+            col += 1;
+        }
+    }
+    loop {
+        while col < kinds.len() && kinds[col] == WordKind::Blank {
+            col += 1;
+        }
+        if col < kinds.len() || row + 1 == data.len() {
+            return (row, col);
+        }
+        row += 1;
+        col = 0;
+        kinds = self::kinds(data, word_chars, row);
+        if kinds.is_empty() {
+            return (row, 0);
+        }
+    }
+}
+
+fn previous_word(data: &Data, word_chars: &str, (mut row, mut col): (usize, usize)) -> (usize, usize) {
+    let mut kinds = kinds(data, word_chars, row);
+    loop {
+        if col == 0 {
+            if row == 0 {
+                return (0, 0);
+            }
+            row -= 1;
+            kinds = self::kinds(data, word_chars, row);
+            col = kinds.len();
+            if col == 0 {
+                return (row, 0);
+            }
+        }
+        col -= 1;
+        if kinds[col] != WordKind::Blank {
+            break;
+        }
+    }
+    while col > 0 && kinds[col - 1] == kinds[col] {
+        col -= 1;
+    }
+    (row, col)
+}
+
+/* Last grapheme of the word under the cursor. With step, the cursor first
+ * moves forward, so that it goes to the end of the next word when it is
+ * already at the end of one. */
+fn word_end(data: &Data, word_chars: &str, (mut row, col): (usize, usize), step: bool) -> (usize, usize) {
+    let mut kinds = kinds(data, word_chars, row);
+    let mut col = col + step as usize;
+    loop {
+        while col < kinds.len() && kinds[col] == WordKind::Blank {
+            col += 1;
+        }
+        if col < kinds.len() {
+            break;
+        }
+        if row + 1 == data.len() {
+            return (row, kinds.len().saturating_sub(1));
+        }
+        row += 1;
+        col = 0;
+        kinds = self::kinds(data, word_chars, row);
+    }
+    while col + 1 < kinds.len() && kinds[col + 1] == kinds[col] {
+        col += 1;
+    }
+    (row, col)
+}
@@ -1,142 +1,975 @@
+use std::env;
 use std::fs;
-use std::fs::File;
-use std::path::Path;
 use std::io::stdout;
-use std::io::Write;
+use std::process;
+use std::time::Duration;
 
+use regex::{ Captures, Regex };
+use termion::event::Key;
 use termion::raw::IntoRawMode;
 
-use super::data::Data;
+use super::buffer::Buffer;
+use super::clipboard::{ Clipboard, Paste };
 use super::config::Config;
-use super::interface::run_prompt;
+use super::data::Data;
+use super::history::Change;
+use super::input::{ Event, Input, Typed };
+use super::keymap::Command;
+use super::languages::LANGUAGE_NAMES;
+use super::lib;
+use super::modal::{ Modal, Mode };
+use super::interface::{ read_key, run_picker, run_prompt, run_prompt_with };
 use super::term::Term;
+use super::theme::THEME_NAMES;
+
+// Swap files are written when no key is pressed for a while, or after many keys
+const SWAP_IDLE: Duration = Duration::from_secs(2);
+const SWAP_KEYS: usize = 200;
 
 pub struct State {
-    term   : Term,
-    data   : Data,
-    config : Config,
+    term            : Term,
+    buffers         : Vec<Buffer>,
+    modal           : Modal,
+    input           : Input,
+    keys_since_swap : usize,
+    // where the selection started, the cursor being its other end
+    anchor          : Option<(usize, usize)>,
+    clipboard       : Clipboard,
+    pasted          : Option<Paste>,
+    config          : Config,
 }
 
 impl State {
     fn new(config: Config) -> State {
         let stdout = stdout().into_raw_mode().unwrap();
         State {
-            term: Term::new(0, 0, 0, 0, stdout),
-            data: Data::from_vec(Vec::new()),
+            term: Term::new(stdout),
+            buffers: Vec::new(),
+            modal: Modal::new(),
+            input: Input::new(),
+            keys_since_swap: 0,
+            anchor: None,
+            clipboard: Clipboard::new(),
+            pasted: None,
             config,
         }
     }
 
-    pub fn create(config: Config) -> State {
+    // Fails when a file named on the command line can't be opened
+    pub fn create(config: Config) -> Result<State, String> {
         let mut state = State::new(config);
+        state.handle_files()?;
         state.term.start(&state.config);
-        state.handle_file();
+        for index in 0 .. state.buffers.len() {
+            state.check_swap(index);
+        }
+        state.draw_text();
         state.draw_status_line();
         state.go_to(0, 0);
-        state
+        if state.config.modal() {
+            state.toggle_modal();
+        }
+        Ok(state)
     }
 
-    fn handle_file(&mut self) {
-        if Path::new(self.config.file_name()).exists() {
-            let input_text = fs::read_to_string(&self.config.file_name()).unwrap();
-            if input_text.is_empty() {
-                self.add_row(String::new());
-            } else {
-                input_text.lines()
-                          .for_each(|line| self.add_row(line.chars().collect()));
+    fn handle_files(&mut self) -> Result<(), String> {
+        for name in self.config.file_names() {
+            let buffer = Buffer::open(name, &self.config)
+                                .map_err(|error| format!("Can't open {}: {}", name, error))?;
+            self.buffers.push(buffer);
+        }
+        if self.buffers.is_empty() {
+            self.buffers.push(Buffer::new(&self.config));
+        }
+        Ok(())
+    }
+
+    /* Offers to recover the text of a swap file, left by an editor that
+     * stopped before saving it. */
+    fn check_swap(&mut self, index: usize) {
+        let swapped = match self.buffers[index].newer_swap() {
+            Some(text) if text != self.buffers[index].data.to_string() => text,
+            Some(_)    => { self.buffers[index].remove_swap(); return; }
+            None       => return,
+        };
+        let name = self.buffers[index].display_name();
+        let question = format!("Found unsaved changes to {}: (r)ecover, (d)iff, (x) discard, \
+                                Esc to decide later", name);
+        loop {
+            match read_key(&question, self) {
+                Key::Char('r') => { self.buffers[index].recover(&swapped); break; }
+                Key::Char('x') => { self.buffers[index].remove_swap(); break; }
+                Key::Char('d') => {
+                    let lines = lib::diff_lines(&self.buffers[index].data.to_string(), &swapped);
+                    let title = format!("Changes to {} (Esc to go back)", name);
+                    run_picker(&title, &lines, 0, self);
+                }
+                Key::Esc => break,
+                _        => (),
+            }
+        }
+        self.draw_all();
+    }
+
+    /* Waits for the next key, writing the swap files when nothing is typed
+     * for a while. Returns None once there are no more keys to read. A paste
+     * comes key by key, for the prompts and pickers. */
+    pub fn next_key(&mut self) -> Option<Key> {
+        loop {
+            match self.next_input()? {
+                Typed::Key(key)    => return Some(key),
+                Typed::Paste(text) => self.input.type_out(&text),
+            }
+        }
+    }
+
+    // The next key, or a text pasted in the terminal
+    pub fn next_input(&mut self) -> Option<Typed> {
+        loop {
+            let typed = match self.input.next(SWAP_IDLE) {
+                Event::Key(key)    => Typed::Key(key),
+                Event::Paste(text) => Typed::Paste(text),
+                Event::Idle        => { self.write_swaps(); self.present(); continue; }
+                Event::Closed      => return None,
+                Event::Terminate   => self.terminate(),
+                Event::Resize      => { self.resize(); self.present(); continue; }
+            };
+            self.keys_since_swap += 1;
+            if self.keys_since_swap >= SWAP_KEYS {
+                self.write_swaps();
+            }
+            return Some(typed);
+        }
+    }
+
+    fn resize(&mut self) {
+        if let Ok((width, height)) = termion::terminal_size() {
+            self.config.set_size(height, width);
+            self.term.resize(&self.buffers, &self.config);
+        }
+    }
+
+    fn write_swaps(&mut self) {
+        self.keys_since_swap = 0;
+        if !self.config.swap() {
+            return;
+        }
+        for index in 0 .. self.buffers.len() {
+            if let Err(err) = self.buffers[index].write_swap() {
+                let name = self.buffers[index].display_name();
+                self.set_message(&format!("Can't write the swap file of {}: {}", name, err));
             }
-            self.term.draw_text(&self.data, &self.config);
-        } else {
-            // we need at least one row, otherwise it won't be possible to write
-            self.add_row(String::new());
         }
     }
+
+    // The buffer shown in the active window
+    fn buffer(&self) -> &Buffer {
+        &self.buffers[self.term.buffer()]
+    }
+
+    fn buffer_mut(&mut self) -> &mut Buffer {
+        &mut self.buffers[self.term.buffer()]
+    }
     
     pub fn save_file(&mut self) {
         let file_name =
-        if !self.config.file_name().is_empty() {
-            self.config.file_name().clone()
+        if !self.buffer().file_name().is_empty() {
+            self.buffer().file_name().clone()
         } else {
-            let name = run_prompt("Enter the file name: ", self);
-            self.config.set_file_name(&name);
-            name
+            match run_prompt("Enter the file name: ", self) {
+                Some(name) => {
+                    let index = self.term.buffer();
+                    self.buffers[index].set_file_name(&name, &self.config);
+                    name
+                }
+                None => { self.set_message("Save aborted."); return; }
+            }
+        };
+        match self.buffer().save(self.config.backup()) {
+            Ok(()) => {
+                self.buffer_mut().history.mark_saved();
+                self.buffer_mut().remove_swap();
+                self.draw_status_line();
+                self.set_message(&format!("File {} written.", file_name)[..]);
+            }
+            Err(err) => self.set_message(&format!("Can't write {}: {}", file_name, err)),
+        }
+        self.draw_text();
+    }
+
+    // Opens another file in a new buffer and switches to it
+    pub fn open_file(&mut self) {
+        let name = match run_prompt("Open file: ", self) {
+            Some(name) if !name.is_empty() => name,
+            _ => return,
         };
-        let editor_text = self.data.to_string();
-        let mut file = File::create(Path::new(&file_name)).unwrap();
+        match self.buffers.iter().position(|buffer| buffer.file_name() == &name) {
+            Some(index) => self.switch_to(index),
+            None        => match Buffer::open(&name, &self.config) {
+                Ok(buffer) => {
+                    self.buffers.push(buffer);
+                    self.switch_to(self.buffers.len() - 1);
+                    self.check_swap(self.buffers.len() - 1);
+                }
+                Err(error) => self.set_message(&format!("Can't open {}: {}", name, error)),
+            }
+        }
+    }
+
+    // Moves through the open buffers, wrapping around the ends of the list
+    pub fn cycle_buffer(&mut self, delta: isize) {
+        let len = self.buffers.len() as isize;
+        let index = (self.term.buffer() as isize + delta).rem_euclid(len);
+        self.switch_to(index as usize);
+    }
+
+    pub fn pick_buffer(&mut self) {
+        let names: Vec<String> = self.buffers.iter().map(|buffer| buffer.display_name()).collect();
+        let current = self.term.buffer();
+        let picked = run_picker("Buffers (Enter to open, Esc to cancel)", &names, current, self);
+        if let Some(index) = picked {
+            self.switch_to(index);
+        }
+        self.draw_all();
+    }
+
+    // Switches to another of the bundled themes, for the rest of the session
+    pub fn pick_theme(&mut self) {
+        let names: Vec<String> = THEME_NAMES.iter().map(|name| name.to_string()).collect();
+        let current = THEME_NAMES.iter().position(|&name| name == self.config.theme().name());
+        let picked = run_picker("Themes (Enter to use, Esc to cancel)", &names,
+                                current.unwrap_or(0), self);
+        if let Some(index) = picked {
+            self.config.set_theme(THEME_NAMES[index]);
+        }
+        self.draw_all();
+    }
+
+    // Highlights the current buffer as another language than the detected one
+    pub fn set_language(&mut self) {
+        let names: Vec<String> = LANGUAGE_NAMES.iter().map(|name| name.to_string()).collect();
+        let current = LANGUAGE_NAMES.iter().position(|&name| name == self.buffer().language());
+        let picked = run_picker("Languages (Enter to use, Esc to cancel)", &names,
+                                current.unwrap_or(0), self);
+        if let Some(index) = picked {
+            self.buffer_mut().set_language(LANGUAGE_NAMES[index]);
+        }
+        self.draw_all();
+        if let Some(index) = picked {
+            self.set_message(&format!("Highlighting as {}.", LANGUAGE_NAMES[index]));
+        }
+    }
+
+    /* Closes the current buffer. When it is the last one, it is replaced by
+     * an empty buffer instead, so the editor keeps running. */
+    pub fn close_buffer(&mut self) {
+        let name = self.buffer().display_name();
+        if self.buffer().is_modified() && !self.confirm(&format!("{} has unsaved changes.", name)) {
+            return;
+        }
+        let index = self.term.buffer();
+        self.buffers.remove(index).remove_swap();
+        if self.buffers.is_empty() {
+            self.buffers.push(Buffer::new(&self.config));
+        }
+        self.term.buffer_closed(index, &self.buffers);
+        self.draw_all();
+        self.set_message(&format!("Closed {}.", name));
+    }
+
+    fn switch_to(&mut self, index: usize) {
+        self.buffer_mut().view = self.term.view();
+        self.term.set_buffer(index);
+        let view = self.buffer().view;
+        self.term.set_view(view, &self.buffers, &self.config);
+        self.draw_status_line();
+    }
+
+    // Splits the active window, vertically meaning side by side
+    pub fn split_window(&mut self, vertical: bool) {
+        match self.term.split(vertical, &self.config) {
+            Ok(())   => self.draw_all(),
+            Err(msg) => self.set_message(msg),
+        }
+    }
+
+    pub fn close_window(&mut self) {
+        match self.term.close_window(&self.config) {
+            Ok(())   => self.draw_all(),
+            Err(msg) => self.set_message(msg),
+        }
+    }
+
+    pub fn next_window(&mut self) {
+        self.buffer_mut().history.seal();
+        self.term.next_window();
+        self.draw_status_line();
+    }
+
+    fn draw_all(&mut self) {
+        self.term.draw_all(&self.buffers, &self.config);
+    }
+
+    fn draw_text(&mut self) {
+        self.term.draw_text(&self.buffers, &self.config);
+    }
+
+    pub fn cursor(&self) -> (usize, usize) {
+        self.term.cursor()
+    }
+
+    // Text of the buffer in the active window
+    pub fn data(&self) -> &Data {
+        &self.buffer().data
+    }
+
+    // Chars that are part of words in the language of the active buffer
+    pub fn word_chars(&self) -> &'static str {
+        self.buffer().word_chars()
+    }
+
+    // The command bound to the key in the config, if any
+    pub fn command_for(&self, key: Key) -> Option<Command> {
+        self.config.keys().get(key)
+    }
+
+    pub fn modal(&mut self) -> &mut Modal {
+        &mut self.modal
+    }
 
-        file.write(editor_text.as_bytes()).unwrap();
-        self.set_message(&format!("File {} written.", file_name)[..]);
-        self.term.draw_text(&self.data, &self.config);
+    /* Removes the text from start up to end (exclusive), recording it for
+     * undo, and leaves the cursor at start. Returns the removed text. An
+     * empty range changes nothing, so nothing is recorded. */
+    pub fn remove_range(&mut self, start: (usize, usize), end: (usize, usize)) -> String {
+        if start >= end {
+            self.go_to(start.0, start.1);
+            return String::new();
+        }
+        let before = self.cursor();
+        let removed = self.buffer_mut().data.remove_text(start.0, start.1, end.0, end.1);
+        let change = Change::Remove { row: start.0, col: start.1, text: removed.clone() };
+        self.buffer_mut().history.record(change, before, start);
+        self.draw_change(start.0, &removed);
+        self.go_to(start.0, start.1);
+        removed
+    }
+
+    /* Inserts text at the given position, recording it for undo, and moves
+     * the cursor right after it. */
+    pub fn insert_at(&mut self, row: usize, col: usize, text: &str) -> (usize, usize) {
+        let before = self.cursor();
+        let after = self.buffer_mut().data.insert_text(row, col, text);
+        let change = Change::Insert { row, col, text: text.to_string() };
+        self.buffer_mut().history.record(change, before, after);
+        self.draw_change(row, text);
+        self.go_to(after.0, after.1);
+        after
+    }
+
+    // Redraws what an edit of text at the given row may have changed
+    fn draw_change(&mut self, row: usize, text: &str) {
+        if text.contains('\n') {
+            self.draw_text();
+        } else {
+            self.term.draw_row(row, &self.buffers, &self.config);
+        }
     }
 
-    fn current_row(&mut self) -> &mut String {
-        self.data.get_row_mut(self.term.row)
+    // Turns the vi-style editing layer on and off
+    pub fn toggle_modal(&mut self) {
+        self.modal.enabled = !self.modal.enabled;
+        if self.modal.enabled {
+            self.set_mode(Mode::Normal);
+            self.set_message("Modal editing on.");
+        } else {
+            self.set_selection(None);
+            self.term.mode = None;
+            self.draw_status_line();
+            self.set_message("Modal editing off.");
+        }
     }
 
-    fn insert_row(&mut self, index: u16, row: String) {
-        self.data.insert(index, row);
-        self.term.add_row(&self.data, &self.config);
+    pub fn set_mode(&mut self, mode: Mode) {
+        self.modal.mode = mode;
+        self.term.mode = Some(mode.name());
+        self.draw_status_line();
     }
 
-    fn insert_char(&mut self, row: u16, col: u16, c: char) {
-        self.data.insert_char(row, col, c);
-        self.term.draw_row(row, &self.data, &self.config);
+    // Shows the given range (both ends included) as selected
+    pub fn set_selection(&mut self, selection: Option<((usize, usize), (usize, usize))>) {
+        self.term.selection = selection;
+        self.draw_text();
     }
 
     pub fn place_char(&mut self, c: char) {
-        self.insert_char(self.term.row, self.term.col, c);
-        self.move_cursor(0, 1);
+        if self.buffer().dedents(c) && self.place_dedented(c) {
+            return;
+        }
+        let (row, col) = self.cursor();
+        let (_, new_col) = self.buffer_mut().data.insert_text(row, col, &c.to_string());
+        self.term.draw_row(row, &self.buffers, &self.config);
+        if new_col == col && col > 0 {
+            /* c is a combining mark that joined the previous grapheme, so it
+             * is recorded as replacing that grapheme to keep columns valid */
+            let text = self.buffer().data.get_row(row);
+            let merged = &text[lib::byte_index(&text, col - 1)
+                               .. lib::byte_index(&text, col)];
+            let previous = &merged[.. merged.len() - c.len_utf8()];
+            let changes = vec![
+                Change::Remove { row, col: col - 1, text: previous.to_string() },
+                Change::Insert { row, col: col - 1, text: merged.to_string() },
+            ];
+            self.buffer_mut().history.record_group(changes, (row, col), (row, col));
+        } else {
+            let change = Change::Insert { row, col, text: c.to_string() };
+            self.buffer_mut().history.record_typed(change, (row, col), (row, new_col));
+        }
+        self.go_to(row, new_col);
     }
 
+    /* Types a char that closes a block, like } in Rust, a level of
+     * indentation to the left, if there is only indentation before it.
+     * Returns false otherwise, leaving the row as it was. */
+    fn place_dedented(&mut self, c: char) -> bool {
+        let (row, col) = self.cursor();
+        let tabs = self.buffer().tabs(&self.config);
+        let start = match self.buffer().dedent_start(row, col, tabs) {
+            Some(start) => start,
+            None        => return false,
+        };
+        let removed = self.buffer_mut().data.remove_text(row, start, row, col);
+        self.buffer_mut().data.insert_text(row, start, &c.to_string());
+        let changes = vec![
+            Change::Remove { row, col: start, text: removed },
+            Change::Insert { row, col: start, text: c.to_string() },
+        ];
+        self.buffer_mut().history.record_group(changes, (row, col), (row, start + 1));
+        self.term.draw_row(row, &self.buffers, &self.config);
+        self.go_to(row, start + 1);
+        true
+    }
+
+    /* A tab character with hard tabs, otherwise spaces up to the next
+     * multiple of the tab width on the screen. */
+    pub fn insert_tab(&mut self) {
+        let tabs = self.buffer().tabs(&self.config);
+        if tabs.hard {
+            self.place_char('\t');
+            return;
+        }
+        let (row, col) = self.cursor();
+        let cell = lib::display_col(&self.data().get_row(row), col, tabs.width as usize);
+        for _ in 0 .. tabs.width as usize - cell % tabs.width as usize {
+            self.place_char(' ');
+        }
+    }
+
+    // Starts a new row, indented from the one above (see Buffer::indent_after)
     pub fn break_line(&mut self) {
-        let col = self.term.col as usize; // avoid two uses of self in the same instruction
-        let chars: String =
-            self.current_row()[col ..].to_string();
-        self.data.truncate_row(self.term.row, self.term.col);
-        self.insert_row(self.term.row + 1, chars);
-        self.term.draw_text(&self.data, &self.config);
-        self.go_to(self.term.row + 1, 0);
+        let (row, col) = self.cursor();
+        let tabs = self.buffer().tabs(&self.config);
+        let text = format!("\n{}", self.buffer().indent_after(row, col, tabs));
+        let after = self.buffer_mut().data.insert_text(row, col, &text);
+        self.draw_text();
+        let change = Change::Insert { row, col, text };
+        self.buffer_mut().history.record(change, (row, col), after);
+        self.go_to(after.0, after.1);
     }
 
     pub fn run_backspace(&mut self) {
-        if self.term.col > 0 {
-            let rem_index = self.term.col - 1;
-            self.data.remove_char(self.term.row, rem_index);
-            self.term.draw_row(self.term.row, &self.data, &self.config);
-            self.go_to(self.term.row, rem_index);
-        } else if self.term.row > 0 {
-            let prev_row = self.term.row - 1;
-            let prev_len = self.data.get_row(prev_row).len();
-            let curr_text = self.current_row().clone();
-            self.data.extend_row(prev_row, curr_text);
-            self.data.remove(self.term.row);
-            self.term.draw_text(&self.data, &self.config);
-            self.go_to(prev_row, prev_len as u16);
+        let (row, col) = self.cursor();
+        if col > 0 {
+            let rem_index = col - 1;
+            let removed = self.buffer_mut().data.remove_text(row, rem_index, row, col);
+            self.term.draw_row(row, &self.buffers, &self.config);
+            let change = Change::Remove { row, col: rem_index, text: removed };
+            self.buffer_mut().history.record(change, (row, col), (row, rem_index));
+            self.go_to(row, rem_index);
+        } else if row > 0 {
+            let prev_row = row - 1;
+            let prev_len = self.buffer().data.row_length(prev_row);
+            self.buffer_mut().data.remove_text(prev_row, prev_len, row, 0);
+            self.draw_text();
+            let change = Change::Remove { row: prev_row, col: prev_len, text: "\n".to_string() };
+            self.buffer_mut().history.record(change, (row, col), (prev_row, prev_len));
+            self.go_to(prev_row, prev_len);
         }
     }
 
-    // Insert row after the last one
-    fn add_row(&mut self, to_add: String) {
-        self.insert_row(self.data.len() as u16, to_add);
+    // Starts a selection at the cursor, or drops the one there is
+    pub fn toggle_mark(&mut self) {
+        if self.anchor.is_some() {
+            self.clear_mark();
+        } else {
+            self.anchor = Some(self.cursor());
+            self.set_message("Mark set.");
+        }
     }
 
-    pub fn move_cursor(&mut self, row_delta: i16, col_delta: i16) {
-        self.term.move_cursor(row_delta, col_delta, &self.data, &self.config);
+    pub fn clear_mark(&mut self) {
+        if self.anchor.take().is_some() {
+            self.set_selection(None);
+        }
     }
 
-    fn go_to(&mut self, row: u16, col: u16) {
-        self.term.go_to(row, col, &self.data, &self.config);
+    // The selected text, from its start up to its end (excluded)
+    fn selected_range(&self) -> Option<((usize, usize), (usize, usize))> {
+        let anchor = self.anchor?;
+        let cursor = self.cursor();
+        Some(if anchor <= cursor { (anchor, cursor) } else { (cursor, anchor) })
     }
-    
+
+    // The selection is drawn with both ends included, as in the Visual mode
+    fn show_selection(&mut self) {
+        let shown = self.selected_range().and_then(|(start, (row, col))| {
+            if col > 0 {
+                Some((start, (row, col - 1)))
+            } else if row > start.0 {
+                Some((start, (row - 1, usize::MAX)))
+            } else {
+                None
+            }
+        });
+        self.set_selection(shown);
+    }
+
+    // Ends the selection for cut and copy, giving its range if it is not empty
+    fn take_selection(&mut self) -> Option<((usize, usize), (usize, usize))> {
+        let range = self.selected_range().filter(|(start, end)| start != end);
+        self.clear_mark();
+        if range.is_none() {
+            self.set_message("Nothing is selected (Ctrl-Space starts a selection).");
+        }
+        range
+    }
+
+    /* Keeps the text in the clipboard, and in the one of the terminal with
+     * osc52. Linewise texts are whole rows, each followed by a newline. */
+    pub fn keep_in_clipboard(&mut self, text: String, linewise: bool) {
+        if self.config.osc52() {
+            self.term.copy_to_terminal(&text);
+        }
+        self.clipboard.push(text, linewise);
+    }
+
+    // The text last cut, copied or yanked, and whether it is linewise
+    pub fn clipboard_text(&self) -> Option<(String, bool)> {
+        self.clipboard.get(0).map(|text| (text.to_string(), self.clipboard.linewise()))
+    }
+
+    pub fn cut(&mut self) {
+        if let Some((start, end)) = self.take_selection() {
+            let removed = self.remove_range(start, end);
+            self.keep_in_clipboard(removed, false);
+        }
+    }
+
+    pub fn copy(&mut self) {
+        if let Some((start, end)) = self.take_selection() {
+            let text = self.data().text_between(start.0, start.1, end.0, end.1);
+            let rows = text.matches('\n').count() + 1;
+            self.keep_in_clipboard(text, false);
+            self.set_message(&format!("Copied {} row{}.", rows, if rows == 1 { "" } else { "s" }));
+        }
+    }
+
+    /* Inserts a text pasted in the terminal as it is, without the indentation
+     * that typing it would add, in a single edit. */
+    pub fn paste_text(&mut self, text: &str) {
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        self.clear_mark();
+        let (row, col) = self.cursor();
+        self.buffer_mut().history.seal();
+        self.insert_at(row, col, &text);
+        self.buffer_mut().history.seal();
+    }
+
+    // Inserts the text last cut or copied at the cursor
+    pub fn paste(&mut self) {
+        let text = match self.clipboard.get(0) {
+            Some(text) => text.to_string(),
+            None       => return self.set_message("The clipboard is empty."),
+        };
+        let start = self.cursor();
+        let end = self.insert_at(start.0, start.1, &text);
+        self.pasted = Some(Paste { start, end, age: 0 });
+    }
+
+    /* Right after a paste, replaces the pasted text by the one cut or copied
+     * before it, going around the clipboard. Undo brings back the text it
+     * replaced. */
+    pub fn paste_older(&mut self) {
+        let Paste { start, end, age } = match self.pasted.filter(|&paste| self.is_last_paste(paste)) {
+            Some(paste) => paste,
+            None        => return self.set_message("Paste older only follows a paste."),
+        };
+        let age = (age + 1) % self.clipboard.len();
+        let text = self.clipboard.get(age).unwrap_or_default().to_string();
+        let data = &mut self.buffer_mut().data;
+        let removed = data.remove_text(start.0, start.1, end.0, end.1);
+        let after = data.insert_text(start.0, start.1, &text);
+        let changes = vec![
+            Change::Remove { row: start.0, col: start.1, text: removed },
+            Change::Insert { row: start.0, col: start.1, text },
+        ];
+        self.buffer_mut().history.record_group(changes, end, after);
+        self.draw_text();
+        self.go_to(after.0, after.1);
+        self.pasted = Some(Paste { start, end: after, age });
+    }
+
+    // Whether the cursor is still right after the text of the paste
+    fn is_last_paste(&self, paste: Paste) -> bool {
+        let Paste { start, end, age } = paste;
+        end == self.cursor()
+            && (end.0) < self.data().len()
+            && self.clipboard.get(age) == Some(&self.data().text_between(start.0, start.1,
+                                                                         end.0, end.1)[..])
+    }
+
+    // Removes the grapheme under the cursor, or joins the next row at the end of a row
+    pub fn delete_forward(&mut self) {
+        let (row, col) = self.cursor();
+        let len = self.data().row_length(row);
+        if col < len {
+            self.remove_range((row, col), (row, col + 1));
+        } else if (row) + 1 < self.data().len() {
+            self.remove_range((row, col), (row + 1, 0));
+        }
+    }
+
+    /* Removes the word after the cursor, or before it, along with the blanks
+     * in between. Words are made of the chars of words in the language, and
+     * the other separators, like operators, make words of their own. At an
+     * end of the row, the rows are joined instead. */
+    pub fn delete_word(&mut self, forward: bool) {
+        let (row, col) = self.cursor();
+        let text = self.data().get_row(row).into_owned();
+        let word_chars = self.word_chars();
+        if forward {
+            match lib::word_end(&text, col, word_chars) {
+                end if end > col => { self.remove_range((row, col), (row, end)); }
+                _                => self.delete_forward(),
+            }
+        } else {
+            match lib::word_start(&text, col, word_chars) {
+                start if start < col => { self.remove_range((row, start), (row, col)); }
+                _                    => self.run_backspace(),
+            }
+        }
+    }
+
+    /* Removes the rest of the row after the cursor, or the start of the row
+     * before it, keeping the text in the clipboard. At the end of a row, the
+     * next one is joined to it. */
+    pub fn kill_line(&mut self, to_end: bool) {
+        let (row, col) = self.cursor();
+        let len = self.data().row_length(row);
+        let removed = match (to_end, col) {
+            (true, col) if col < len => self.remove_range((row, col), (row, len)),
+            (true, _)                => { self.delete_forward(); return; }
+            (false, 0)               => return,
+            (false, col)             => self.remove_range((row, 0), (row, col)),
+        };
+        self.keep_in_clipboard(removed, false);
+    }
+
+    // Removes the row of the cursor, keeping it in the clipboard
+    pub fn delete_line(&mut self) {
+        let (row, col) = self.cursor();
+        let (removed, row) = self.remove_rows(row, row);
+        self.keep_in_clipboard(removed, true);
+        self.go_to(row, col);
+    }
+
+    /* Removes the rows from first to last. Returns their text, each row
+     * followed by a newline, and the row that the cursor can go to. */
+    pub fn remove_rows(&mut self, first: usize, last: usize) -> (String, usize) {
+        let rows = self.data().len();
+        let last_len = self.data().row_length(last);
+        // the buffer keeps at least one row, even if it is empty
+        if last + 1 < rows {
+            (self.remove_range((first, 0), (last + 1, 0)), first)
+        } else if first > 0 {
+            // the newline before the first row goes instead of the one after the last
+            let previous_len = self.data().row_length(first - 1);
+            let removed = self.remove_range((first - 1, previous_len), (last, last_len));
+            (format!("{}\n", &removed[1 ..]), first - 1)
+        } else {
+            (self.remove_range((first, 0), (last, last_len)) + "\n", first)
+        }
+    }
+
+    /* Incremental search: the cursor jumps to the next match while the query
+     * is typed, Up and Down go to the previous and next matches, and Escape
+     * brings the cursor back to where the search started. */
+    pub fn find(&mut self) {
+        let start = self.term.view();
+        let query = run_prompt_with("Search (Esc to cancel, Up/Down to move): ",
+                                    self,
+                                    |state, query, key| state.find_step(query, key));
+        self.term.highlight = None;
+        match query {
+            Some(_) => self.draw_text(),
+            None    => self.term.set_view(start, &self.buffers, &self.config),
+        }
+    }
+
+    fn find_step(&mut self, query: &str, key: Key) {
+        let (row, col) = self.cursor();
+        let found = match key {
+            Key::Down => self.buffer().data.find(query, row, col + 1, true),
+            Key::Up   => self.buffer().data.find(query, row, col, false),
+            _         => self.buffer().data.find(query, row, col, true),
+        };
+        self.term.highlight = Some(query.to_string());
+        self.draw_text();
+        if let Some((row, col)) = found {
+            self.go_to(row, col);
+        }
+    }
+
+    /* Replaces the matches of a pattern from the top of the file, asking for
+     * each one. With use_regex the pattern is a regular expression and the
+     * replacement may refer to its groups as $1, $name, etc. All the
+     * replacements are undone together. */
+    pub fn replace(&mut self, use_regex: bool) {
+        let pattern = match run_prompt("Replace: ", self) {
+            Some(pattern) if !pattern.is_empty() => pattern,
+            _ => return,
+        };
+        let regex =
+            match Regex::new(&if use_regex { pattern.clone() } else { regex::escape(&pattern) }) {
+                Ok(regex) => regex,
+                Err(_)    => { self.set_message(&format!("Invalid pattern: {}", pattern)); return; }
+            };
+        let replacement = match run_prompt(&format!("Replace {} with: ", pattern), self) {
+            Some(replacement) => replacement,
+            None              => return,
+        };
+
+        let before = self.cursor();
+        let mut changes = Vec::new();
+        let mut replace_all = false;
+        let mut row = 0;
+        'rows: while (row) < self.buffer().data.len() {
+            let mut search_from = 0;
+            loop {
+                let text = self.buffer().data.get_row(row).into_owned();
+                let captures = match grapheme_match(&regex, &text, search_from) {
+                    Some(captures) => captures,
+                    None           => break,
+                };
+                let found = captures.get(0).unwrap();
+                let col = lib::grapheme_count(&text[.. found.start()]);
+                let end_col = col + lib::grapheme_count(found.as_str());
+
+                if !replace_all {
+                    self.term.highlight = Some(found.as_str().to_string());
+                    self.draw_text();
+                    self.go_to(row, col);
+                    match read_key("Replace this match? (y/n/a/q)", self) {
+                        Key::Char('y') => (),
+                        Key::Char('a') => replace_all = true,
+                        Key::Char('n') => {
+                            search_from = next_search(&text, found.start(), found.end());
+                            continue;
+                        }
+                        _ => break 'rows,
+                    }
+                }
+
+                let mut replaced = String::new();
+                if use_regex {
+                    captures.expand(&replacement, &mut replaced);
+                } else {
+                    replaced.push_str(&replacement);
+                }
+                let removed = self.buffer_mut().data.remove_text(row, col, row, end_col);
+                self.buffer_mut().data.insert_text(row, col, &replaced);
+                search_from = next_search(&text, found.start(), found.end())
+                              + replaced.len() - found.as_str().len();
+                changes.push(Change::Remove { row, col, text: removed });
+                changes.push(Change::Insert { row, col, text: replaced });
+            }
+            row += 1;
+        }
+
+        self.term.highlight = None;
+        self.draw_text();
+        let count = changes.len() / 2;
+        if count > 0 {
+            let after = self.cursor();
+            self.buffer_mut().history.record_group(changes, before, after);
+        }
+        self.set_message(&format!("Replaced {} occurrence(s).", count));
+    }
+
+    pub fn undo(&mut self) {
+        let buffer = self.buffer_mut();
+        match buffer.history.undo(&mut buffer.data) {
+            Some((row, col)) => self.redraw_at(row, col),
+            None             => self.set_message("Nothing to undo."),
+        }
+    }
+
+    pub fn redo(&mut self) {
+        let buffer = self.buffer_mut();
+        match buffer.history.redo(&mut buffer.data) {
+            Some((row, col)) => self.redraw_at(row, col),
+            None             => self.set_message("Nothing to redo."),
+        }
+    }
+
+    fn redraw_at(&mut self, row: usize, col: usize) {
+        self.draw_text();
+        self.go_to(row, col);
+    }
+
+    pub fn move_cursor(&mut self, row_delta: isize, col_delta: isize) {
+        self.buffer_mut().history.seal();
+        self.term.move_cursor(row_delta, col_delta, &self.buffers, &self.config);
+        if self.anchor.is_some() {
+            self.show_selection();
+        }
+    }
+
+    pub fn go_to(&mut self, row: usize, col: usize) {
+        self.term.go_to(row, col);
+    }
+
+    // Shows what was drawn since the last time, with the cursor in its place
+    pub fn present(&mut self) {
+        self.term.present(&self.buffers, &self.config);
+    }
+
     pub fn set_message(&mut self, msg: &str) {
-        self.term.set_message(msg, &self.data, &self.config);
+        self.term.set_message(msg, &self.config);
+    }
+
+    pub fn draw_picker(&mut self, title: &str, items: &[String], selected: usize) {
+        self.term.draw_picker(title, items, selected, &self.config);
     }
 
     pub fn draw_status_line(&mut self) {
-        self.term.draw_status_line(&self.data, &self.config);
+        self.term.draw_status_line(&self.buffers, &self.config);
+    }
+
+    // Quits, after asking when some of the buffers have unsaved changes
+    pub fn quit(&mut self) {
+        let unsaved: Vec<String> = self.buffers.iter()
+                                               .filter(|buffer| buffer.is_modified())
+                                               .map(|buffer| buffer.display_name())
+                                               .collect();
+        if unsaved.is_empty() || self.confirm(&format!("Unsaved changes in {}.", unsaved.join(", "))) {
+            self.buffers.iter_mut().for_each(Buffer::remove_swap);
+            self.die();
+        }
+    }
+
+    // Asks whether to go on with something that loses unsaved changes
+    fn confirm(&mut self, msg: &str) -> bool {
+        let key = read_key(&format!("{} Discard them? (y/n)", msg), self);
+        key == Key::Char('y')
     }
 
     pub fn die(&mut self) {
-        self.term.die(&self.config);
+        self.term.die();
+    }
+
+    /* Writes the unsaved changes of every buffer where they can be found
+     * after a crash: in the swap files, which are offered for recovery when
+     * the files are opened again, or in the temporary directory for the
+     * buffers without a name. Returns the files written. */
+    pub fn rescue(&mut self) -> Vec<String> {
+        let mut written = Vec::new();
+        for (index, buffer) in self.buffers.iter().enumerate() {
+            if !buffer.is_modified() {
+                continue;
+            }
+            let saved = match buffer.swap_path() {
+                Some(path) => buffer.save_swap(&path).map(|_| path),
+                None       => {
+                    let name = format!("functional-editor-{}-{}.txt", process::id(), index);
+                    let path = env::temp_dir().join(name);
+                    fs::write(&path, buffer.data.to_string()).map(|_| path)
+                }
+            };
+            if let Ok(path) = saved {
+                written.push(path.display().to_string());
+            }
+        }
+        written
+    }
+
+    // Stops after a signal asking to, keeping the unsaved changes
+    fn terminate(&mut self) -> ! {
+        let written = self.rescue();
+        self.term.restore();
+        for path in written {
+            eprintln!("Unsaved changes written to {}", path);
+        }
+        process::exit(1);
+    }
+}
+
+/* The first match in text from a byte index on that starts and ends between
+ * graphemes. The others, like an accent matched without its letter, would
+ * have the whole grapheme replaced. */
+fn grapheme_match<'t>(regex: &Regex, text: &'t str, from: usize) -> Option<Captures<'t>> {
+    let mut from = from;
+    while from <= text.len() {
+        let captures = regex.captures_at(text, from)?;
+        let found = captures.get(0).unwrap();
+        if lib::is_grapheme_boundary(text, found.start())
+           && lib::is_grapheme_boundary(text, found.end()) {
+            return Some(captures);
+        }
+        from = next_search(text, found.start(), found.end());
+    }
+    None
+}
+
+// Where to look for the next match after one spanning [start, end) in text
+fn next_search(text: &str, start: usize, end: usize) -> usize {
+    if start < end {
+        end
+    } else {
+        // step over empty matches so that the search moves forward
+        end + text[end ..].chars().next().map_or(1, |c| c.len_utf8())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::Regex;
+
+    use super::grapheme_match;
+
+    fn match_range(pattern: &str, text: &str) -> Option<(usize, usize)> {
+        let regex = Regex::new(pattern).unwrap();
+        grapheme_match(&regex, text, 0).map(|captures| {
+            let found = captures.get(0).unwrap();
+            (found.start(), found.end())
+        })
+    }
+
+    #[test]
+    fn matches_inside_a_grapheme_are_skipped() {
+        // the first e has a combining accent, the second doesn't
+        let text = "cafe\u{301} tea";
+        assert_eq!(match_range("e", text), Some((8, 9)));
+        assert_eq!(match_range("\u{301}", text), None);
+        assert_eq!(match_range("fe", text), None);
+        assert_eq!(match_range("fe\u{301}", text), Some((2, 6)));
+        assert_eq!(match_range("caf", text), Some((0, 3)));
+    }
+
+    #[test]
+    fn empty_matches_move_forward() {
+        assert_eq!(match_range("x*", "e\u{301}"), Some((0, 0)));
+        assert_eq!(grapheme_match(&Regex::new("x*").unwrap(), "e\u{301}", 1)
+                       .map(|captures| captures.get(0).unwrap().start()),
+                   Some(3));
     }
 }
@@ -0,0 +1,134 @@
+use std::cmp::{ max, min };
+use std::ops::Range;
+
+use streaming_iterator::StreamingIterator;
+use tree_sitter::{ InputEdit, Language, Node, Parser, Point, Query, QueryCursor, Tree };
+
+use super::data::Data;
+use super::history::Change;
+
+// The grammar and the highlight query of a language, for the ones that have them
+fn grammar(language: &str) -> Option<(Language, &'static str)> {
+    match language {
+        "elm"     => Some((tree_sitter_elm::LANGUAGE.into(), tree_sitter_elm::HIGHLIGHTS_QUERY)),
+        "haskell" => Some((tree_sitter_haskell::LANGUAGE.into(), tree_sitter_haskell::HIGHLIGHTS_QUERY)),
+        "ocaml"   => Some((tree_sitter_ocaml::LANGUAGE_OCAML.into(), tree_sitter_ocaml::HIGHLIGHTS_QUERY)),
+        "rust"    => Some((tree_sitter_rust::LANGUAGE.into(), tree_sitter_rust::HIGHLIGHTS_QUERY)),
+        _         => None,
+    }
+}
+
+/* A buffer parsed with the tree-sitter grammar of its language. The tree is
+ * brought up to date when it is used after an edit: the changes kept by the
+ * data are told to it, and only the part around them is parsed again. */
+pub struct SyntaxTree {
+    parser   : Parser,
+    query    : Query,
+    tree     : Option<Tree>,
+    // the text as it was when last parsed
+    text     : Data,
+    revision : Option<usize>,
+}
+
+impl SyntaxTree {
+    // None if there is no grammar for the language
+    pub fn new(language: &str) -> Option<SyntaxTree> {
+        let (language, highlights) = grammar(language)?;
+        let mut parser = Parser::new();
+        parser.set_language(&language).ok()?;
+        let query = Query::new(&language, highlights).ok()?;
+        Some(SyntaxTree {
+            parser, query, tree: None, text: Data::from_vec(vec![String::new()]), revision: None,
+        })
+    }
+
+    fn update(&mut self, data: &Data) {
+        if self.revision == Some(data.revision()) {
+            return;
+        }
+        match (data.take_edits(), &mut self.tree) {
+            (Some(changes), Some(tree)) => for change in changes {
+                tree.edit(&input_edit(&self.text, &change));
+                change.apply(&mut self.text);
+            },
+            // the first parse, or the changes weren't kept
+            _ => self.tree = None,
+        }
+        self.text = data.clone();
+        self.text.forget_edits();
+        self.tree = self.parser.parse_with_options(&mut |byte, _| data.chunk_at(byte),
+                                                   self.tree.as_ref(), None);
+        self.revision = Some(data.revision());
+    }
+
+    /* The style of each byte of the row. The captures of a node are drawn
+     * over the ones of the nodes around it, like an escape in a string, and
+     * when several patterns capture the same node the first one is kept, as
+     * the highlight queries expect. */
+    pub fn row_styles(&mut self, data: &Data, row: usize) -> Option<Vec<&'static str>> {
+        self.update(data);
+        let tree = self.tree.as_ref()?;
+        let Range { start, end } = data.row_bytes(row);
+        let mut styles = vec!["default"; end - start];
+        let mut painted = Vec::new();
+        let mut cursor = QueryCursor::new();
+        cursor.set_byte_range(start .. end);
+        let mut captures = cursor.captures(&self.query, tree.root_node(),
+                                           |node: Node| data.byte_chunks(node.byte_range()));
+        while let Some((found, index)) = captures.next() {
+            let capture = found.captures[*index];
+            if painted.contains(&capture.node.id()) {
+                continue;
+            }
+            painted.push(capture.node.id());
+            let style = capture_style(self.query.capture_names()[capture.index as usize]);
+            let from = max(capture.node.start_byte(), start) - start;
+            let to = min(capture.node.end_byte(), end) - start;
+            for byte_style in styles.iter_mut().take(to).skip(from) {
+                *byte_style = style;
+            }
+        }
+        Some(styles)
+    }
+}
+
+// Styles of the captures of the highlight queries, like @function.method or
+// the TextMate scopes of Elm's, like @storage.type
+fn capture_style(name: &str) -> &'static str {
+    match name.split('.').next().unwrap_or(name) {
+        "keyword"              => "keyword",
+        "type" | "constructor" => "type_name",
+        "module" | "namespace" => "type_name",
+        "union" | "storage"    => "type_name",
+        "function"             => "function",
+        "comment"              => "comment",
+        "string" | "escape"    => "string",
+        "char" | "character"   => "string",
+        "number" | "constant"  => "number",
+        "operator"             => "operator",
+        _                      => "default",
+    }
+}
+
+/* The edit of the tree for a change made to old, with the end of the text
+ * found from the newlines in it. */
+fn input_edit(old: &Data, change: &Change) -> InputEdit {
+    let (Change::Insert { row, col, text } | Change::Remove { row, col, text }) = change;
+    let (start_byte, column) = old.byte_position(*row, *col);
+    let start = Point { row: *row, column };
+    let end = match text.rfind('\n') {
+        Some(newline) => Point { row: row + text.matches('\n').count(), column: text.len() - newline - 1 },
+        None          => Point { row: *row, column: column + text.len() },
+    };
+    let end_byte = start_byte + text.len();
+    match change {
+        Change::Insert { .. } => InputEdit {
+            start_byte, old_end_byte: start_byte, new_end_byte: end_byte,
+            start_position: start, old_end_position: start, new_end_position: end,
+        },
+        Change::Remove { .. } => InputEdit {
+            start_byte, old_end_byte: end_byte, new_end_byte: start_byte,
+            start_position: start, old_end_position: end, new_end_position: start,
+        },
+    }
+}
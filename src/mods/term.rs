@@ -2,257 +2,517 @@ use std::io::{Write};
 use std::process;
 
 use termion::*;
+use unicode_segmentation::UnicodeSegmentation;
 
 use super::config::Config;
-use super::data::Data;
+use super::buffer::Buffer;
+use super::clipboard;
+use super::grid::{ Grid, Style };
 use super::lib;
+use super::window::{ self, Layout, View, Window };
 
+const BRACKETED_PASTE_ON: &str = "\x1b[?2004h";
+const BRACKETED_PASTE_OFF: &str = "\x1b[?2004l";
+
+/* The screen, divided in windows. Most of the methods act on the active
+ * window, but whatever is drawn for a buffer is drawn in every window that
+ * shows it, so that an edit is seen in all of them. Drawing is done on a
+ * grid, which is sent to the terminal by present, once per key press. */
 pub struct Term {
-    pub row     : u16,
-    pub col     : u16,
-    vert_offset : u16,
-    hor_offset  : u16,
-    stdout      : raw::RawTerminal<std::io::Stdout>,
+    windows       : Vec<Window>,
+    layout        : Layout,
+    separators    : Vec<(u16, u16, u16)>,
+    active        : usize,
+    pub highlight : Option<String>,
+    pub selection : Option<((usize, usize), (usize, usize))>,
+    pub mode      : Option<&'static str>,
+    // where present puts the cursor instead of the active window, like on a picker
+    cursor_at     : Option<(u16, u16)>,
+    grid          : Grid,
+    stdout        : raw::RawTerminal<std::io::Stdout>,
 }
 
 impl Term {
-    pub fn new(row: u16,
-               col: u16,
-               vert_offset: u16,
-               hor_offset: u16,
-               stdout: raw::RawTerminal<std::io::Stdout>) -> Term {
+    pub fn new(stdout: raw::RawTerminal<std::io::Stdout>) -> Term {
         Term {
-            row,
-            col,
-            vert_offset,
-            hor_offset,
+            windows: vec![Window::new(0)],
+            layout: Layout::Window(0),
+            separators: Vec::new(),
+            active: 0,
+            highlight: None,
+            selection: None,
+            mode: None,
+            cursor_at: None,
+            grid: Grid::new(0, 0),
             stdout,
         }
     }
 
+    /* Draws on the alternate screen, so that the one of the shell is kept.
+     * Pastes are bracketed, so that they can be told apart from typing. */
     pub fn start(&mut self, config: &Config) {
+        self.arrange(config);
         write!(self.stdout,
-               "{}{}",
+               "{}{}{}{}",
+               screen::ToAlternateScreen,
                clear::All,
                cursor::Show,
+               BRACKETED_PASTE_ON,
               ).unwrap();
+        self.grid.resize(config.height() + 2, config.width());
 
-        for row in 1..=config.height() as u16 {
-            write!(self.stdout,
-                   "{}~",
-                   cursor::Goto(1, row)
-                  ).unwrap();
+        self.grid.set_style(config.theme().style("tilde"));
+        for row in 1..=config.height() {
+            self.grid.goto(1, row);
+            self.grid.write("~");
+            self.grid.clear_until_newline();
         }
+        self.grid.set_style(config.theme().style("default"));
+        self.grid.goto(1, config.height() + 2);
+        self.grid.clear_until_newline();
     }
 
-    fn adjust_col(&self, col: u16, config: &Config) -> u16 {
-        col + config.min_col() - self.hor_offset
+    fn win(&self) -> &Window {
+        &self.windows[self.active]
     }
 
-    fn adjust_row(&self, row: u16, config: &Config) -> u16 {
-        row + config.min_row() - self.vert_offset
+    fn win_mut(&mut self) -> &mut Window {
+        &mut self.windows[self.active]
     }
 
-    /* Make sure that self.row and self.col is on a valid position of the file.
-     * In case it get off the screen we increase the offset and re_draw (scroll). */
-    fn fix_cursor_bounds(&mut self, data: &Data, config: &Config) {
-        let mut changed_offset = false;
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.win().row, self.win().col)
+    }
+
+    // Index of the buffer shown in the active window
+    pub fn buffer(&self) -> usize {
+        self.win().buffer
+    }
+
+    pub fn set_buffer(&mut self, buffer: usize) {
+        self.win_mut().buffer = buffer;
+    }
+
+    pub fn view(&self) -> View {
+        self.win().view()
+    }
+
+    pub fn set_view(&mut self, view: View, buffers: &[Buffer], config: &Config) {
+        let buffer = &buffers[self.buffer()];
+        self.win_mut().set_view(view, &buffer.data);
+        self.draw_text(buffers, config);
+    }
 
-        if self.row >= data.len() as u16 {
-            self.row = data.len() as u16 - 1;
+    /* Called after a buffer is removed from buffers. The windows showing it
+     * move to the buffer that took its place, and the others are renumbered. */
+    pub fn buffer_closed(&mut self, buffer: usize, buffers: &[Buffer]) {
+        for window in self.windows.iter_mut() {
+            if window.buffer == buffer {
+                window.buffer = std::cmp::min(buffer, buffers.len() - 1);
+                window.set_view(buffers[window.buffer].view, &buffers[window.buffer].data);
+            } else if window.buffer > buffer {
+                window.buffer -= 1;
+            }
         }
-    
-        if self.row >= config.height() + self.vert_offset {
-            self.vert_offset = self.row - config.height() + 1;
-            changed_offset = true;
-        } else if self.row < self.vert_offset {
-            self.vert_offset = self.row;
-            changed_offset = true;
+    }
+
+    fn arrange(&mut self, config: &Config) {
+        self.separators =
+            self.layout.arrange(&mut self.windows, 1, 1, config.height() + 1, config.width());
+    }
+
+    /* Splits the active window in two, both showing the same buffer at the
+     * same position, and moves to the new one. */
+    pub fn split(&mut self, vertical: bool, config: &Config) -> Result<(), &'static str> {
+        let fits =
+            if vertical {
+                self.win().width > 2 * window::MIN_WIDTH
+            } else {
+                self.win().height + 1 >= 2 * window::MIN_HEIGHT
+            };
+        if !fits {
+            return Err("Not enough room to split the window.");
         }
+        let new_window = self.windows.len();
+        self.windows.push(*self.win());
+        self.layout.split(self.active, new_window, vertical);
+        self.active = new_window;
+        self.arrange(config);
+        Ok(())
+    }
 
-        if self.col > data.row_length(self.row) as u16 {
-            self.col = data.row_length(self.row) as u16;
+    pub fn close_window(&mut self, config: &Config) -> Result<(), &'static str> {
+        if self.windows.len() == 1 {
+            return Err("Can't close the last window.");
         }
+        self.windows.remove(self.active);
+        self.layout.remove(self.active);
+        self.active = std::cmp::min(self.active, self.windows.len() - 1);
+        self.arrange(config);
+        Ok(())
+    }
 
-        if self.col + config.min_col() > config.width() + self.hor_offset {
-            self.hor_offset = self.col + config.min_col() - config.width();
+    pub fn next_window(&mut self) {
+        self.active = (self.active + 1) % self.windows.len();
+    }
+
+    // col is a display column here, see screen_col
+    fn adjust_col(&self, col: usize, config: &Config) -> u16 {
+        (col - self.win().hor_offset) as u16 + self.win().left + config.min_col() - 1
+    }
+
+    fn adjust_row(&self, row: usize) -> u16 {
+        (row - self.win().vert_offset) as u16 + self.win().top
+    }
+
+    /* Display column of the cursor, which differs from its col when the row
+     * has wide characters or tabs. Returns the first and last cells it covers. */
+    fn screen_col(window: &Window, buffer: &Buffer, config: &Config) -> (usize, usize) {
+        let text = buffer.data.get_row(window.row);
+        let tab_width = buffer.tabs(config).width as usize;
+        let start = lib::display_col(&text, window.col, tab_width);
+        let end = lib::display_col(&text, window.col + 1, tab_width);
+        (start, std::cmp::max(start + 1, end) - 1)
+    }
+
+    // Number of cells available for the text of a row, at least one
+    fn text_width(window: &Window, config: &Config) -> u16 {
+        std::cmp::max((window.width + 1).saturating_sub(config.min_col()), 1)
+    }
+
+    /* Make sure that the cursor is on a valid position of the file.
+     * In case it get off the screen we increase the offset and re_draw (scroll). */
+    fn fix_cursor_bounds(&mut self, buffers: &[Buffer], config: &Config) {
+        if self.fix_offsets(self.active, buffers, config) {
+            self.draw_window(self.active, buffers, config);
+        }
+    }
+
+    // Scrolls a window so that its cursor is visible. Tells if it had to.
+    fn fix_offsets(&mut self, index: usize, buffers: &[Buffer], config: &Config) -> bool {
+        let window = &mut self.windows[index];
+        let buffer = &buffers[window.buffer];
+        let mut changed_offset = window.clamp(&buffer.data);
+
+        let height = window.height as usize;
+        if window.row >= height + window.vert_offset {
+            window.vert_offset = window.row + 1 - height;
             changed_offset = true;
-        } else if self.col < self.hor_offset {
-            self.hor_offset = self.col;
+        } else if window.row < window.vert_offset {
+            window.vert_offset = window.row;
             changed_offset = true;
         }
 
-        if changed_offset {
-            self.draw_text(data, config);
+        let (first_cell, last_cell) = Term::screen_col(window, buffer, config);
+        let text_width = Term::text_width(window, config) as usize;
+        if last_cell >= text_width + window.hor_offset {
+            window.hor_offset = last_cell - text_width + 1;
+            changed_offset = true;
+        } else if first_cell < window.hor_offset {
+            window.hor_offset = first_cell;
+            changed_offset = true;
+        }
+        changed_offset
+    }
+
+    /* Fits the windows to the new size of the screen, found in config, and
+     * draws them again. */
+    pub fn resize(&mut self, buffers: &[Buffer], config: &Config) {
+        self.arrange(config);
+        for index in 0 .. self.windows.len() {
+            self.fix_offsets(index, buffers, config);
+        }
+        write!(self.stdout, "{}", clear::All).unwrap();
+        self.grid.resize(config.height() + 2, config.width());
+        self.draw_all(buffers, config);
+    }
+
+    /* The part of a row that fits in the window, with tabs turned into spaces.
+     * Wide characters cut by the left border are replaced by spaces, and the
+     * ones that do not fit in the right border are left out. Also gives the
+     * style of each byte of the result, and whether it is part of a match of
+     * the highlighted text or of the selection, which only shows in the
+     * windows of the active buffer. */
+    fn visible_text(&self, window: &Window, row: usize, buffer: &Buffer, config: &Config)
+                   -> (String, Vec<(&'static str, bool)>) {
+        let left = window.hor_offset;
+        let right = left + Term::text_width(window, config) as usize;
+        let text = buffer.data.get_row(row);
+        let styles = buffer.row_styles(row);
+        let tab_width = buffer.tabs(config).width as usize;
+        let matches: Vec<(usize, usize)> = match &self.highlight {
+            Some(query) if !query.is_empty() =>
+                text.match_indices(&query[..])
+                    .map(|(index, found)| (index, index + found.len()))
+                    .collect(),
+            _ => Vec::new(),
+        };
+        let selection = self.selection.filter(|_| window.buffer == self.buffer());
+        let mut visible = String::new();
+        let mut marks = Vec::new();
+        let mut cell = 0;
+        for (col, (index, grapheme)) in text.grapheme_indices(true).enumerate() {
+            let width = lib::grapheme_width(grapheme, cell, tab_width);
+            if cell + width > right {
+                break;
+            } else if cell >= left {
+                let selected = selection.is_some_and(|(start, end)| {
+                    start <= (row, col) && (row, col) <= end
+                });
+                let marked = selected
+                             || matches.iter().any(|&(start, end)| start <= index && index < end);
+                if grapheme == "\t" {
+                    visible.push_str(&" ".repeat(width));
+                } else {
+                    visible.push_str(grapheme);
+                }
+                marks.resize(visible.len(), (styles[index], marked));
+            } else if cell + width > left {
+                visible.push_str(&" ".repeat(cell + width - left));
+                marks.resize(visible.len(), (styles[index], false));
+            }
+            cell += width;
         }
+        (visible, marks)
     }
 
+    /* Writes text in the styles given by visible_text, inverting them for
+     * the bytes that are marked. */
+    fn write_marked(&mut self, text: &str, marks: &[(&'static str, bool)], language: &str,
+                    config: &Config) {
+        let mut start = 0;
+        while start < text.len() {
+            let (kind, marked) = marks[start];
+            let len = marks[start ..].iter().take_while(|&&mark| mark == (kind, marked)).count();
+            let style = config.style(language, kind);
+            self.grid.set_style(Style { invert: style.invert != marked, ..style });
+            self.grid.write(&text[start .. start + len]);
+            start += len;
+        }
+        self.grid.set_style(config.style(language, "default"));
+    }
 
-    pub fn add_row(&mut self, data: &Data, config: &Config) {
-        if data.len() as u16 <= config.height() + self.vert_offset {
-            write!(self.stdout,
-                   "{}{}{}{}",
-                   color::Fg(color::Yellow),
-                   cursor::Goto(1, data.len() as u16),
-                   data.len(),
-                   color::Fg(color::Reset)
-                  ).unwrap()
+    // Draws a row of the buffer in one window, if the row is visible there
+    fn draw_window_row(&mut self, index: usize, row: usize, buffer: &Buffer, config: &Config) {
+        let window = self.windows[index];
+        if row < window.vert_offset || row >= window.vert_offset + window.height as usize {
+            return;
         }
+        let screen_row = (row - window.vert_offset) as u16 + window.top;
+        let (curr_text, marks) = self.visible_text(&window, row, buffer, config);
+        if config.line_numbers() {
+            self.grid.goto(window.left, screen_row);
+            self.grid.set_style(config.theme().style("line_number"));
+            self.grid.write(&format!("{:<width$}", row + 1, width = config.min_col() as usize - 1));
+        }
+        self.grid.goto(window.left + config.min_col() - 1, screen_row);
+        self.write_marked(&curr_text, &marks, buffer.language(), config);
+        let padding = (Term::text_width(&window, config) as usize)
+                          .saturating_sub(lib::display_width(&curr_text));
+        self.grid.write(&" ".repeat(padding));
     }
 
-    fn set_color(&mut self, from: &str) {
-        match from {
-            "yellow" => write!(self.stdout, "{}", color::Fg(color::Yellow)).unwrap(),
-            "green"  => write!(self.stdout, "{}", color::Fg(color::Green)).unwrap(),
-            "red"    => write!(self.stdout, "{}", color::Fg(color::Red)).unwrap(),
-            "blue"   => write!(self.stdout, "{}", color::Fg(color::Blue)).unwrap(),
-            "white"  => write!(self.stdout, "{}", color::Fg(color::White)).unwrap(),
-            _        => panic!("unknown color"),
+    // Draws all the rows of a window, with '~' after the end of the buffer
+    fn draw_window(&mut self, index: usize, buffers: &[Buffer], config: &Config) {
+        let window = self.windows[index];
+        let buffer = &buffers[window.buffer];
+        let non_empty_rows = std::cmp::min(buffer.data.len().saturating_sub(window.vert_offset),
+                                           window.height as usize) as u16;
+        for row in 0 .. non_empty_rows {
+            self.draw_window_row(index, window.vert_offset + row as usize, buffer, config);
+        }
+        self.grid.set_style(config.theme().style("tilde"));
+        for row in non_empty_rows .. window.height {
+            self.grid.goto(window.left, row + window.top);
+            self.grid.write(&format!("~{}", " ".repeat((window.width as usize).saturating_sub(1))));
         }
     }
 
-    // Assumes row < data.len()
-    pub fn draw_row(&mut self, row: u16, data: &Data, config: &Config) {
-        let row_len = data.row_length(row);
-        let curr_text: &str =
-            if row_len >= self.hor_offset as usize {
-                let right_border =
-                    std::cmp::min((self.hor_offset + config.width()) as usize,
-                                  row_len);
-                &data.get_row(row)[self.hor_offset as usize .. right_border]
+    // Indices of the windows showing the same buffer as the active one
+    fn sharing_windows(&self) -> Vec<usize> {
+        (0 .. self.windows.len()).filter(|&index| self.windows[index].buffer == self.buffer())
+                                 .collect()
+    }
+
+    /* Assumes row < buffer.data.len(). The rows below it are drawn again as
+     * well, since an edit can change their colors, for instance by opening a
+     * comment, and the grid only sends the ones that did change. */
+    pub fn draw_row(&mut self, row: usize, buffers: &[Buffer], config: &Config) {
+        let buffer = &buffers[self.buffer()];
+        for index in self.sharing_windows() {
+            if index != self.active && self.windows[index].clamp(&buffer.data) {
+                self.draw_window(index, buffers, config);
             } else {
-                ""
-            };
-        write!(self.stdout,
-               "{}{}{}{}{}{}",
-               cursor::Goto(1, self.adjust_row(row, config)),
-               clear::UntilNewline,
-               color::Fg(color::Yellow),
-               row + 1,
-               color::Fg(color::Reset),
-               cursor::Goto(config.min_col(), self.adjust_row(row, config))
-              ).unwrap();
-        for (word, whites) in lib::words_and_separators(curr_text) {
-            self.set_color(&config.color_from_word(&word));
-            write!(self.stdout,
-                   "{}{}{}",
-                   word,
-                   color::Fg(color::Reset),
-                   whites)
-                  .unwrap();
+                let window = self.windows[index];
+                let end = std::cmp::min(window.vert_offset + window.height as usize, buffer.data.len());
+                for curr_row in row .. end {
+                    self.draw_window_row(index, curr_row, buffer, config);
+                }
+            }
         }
-        self.rewind(data, config);
-    }
-    
-    pub fn move_cursor(&mut self, row_delta: i16, col_delta: i16, data: &Data, config: &Config) {
-        let real_col_delta = std::cmp::max(col_delta, -(self.col as i16));
-        let real_row_delta = std::cmp::max(row_delta, -(self.row as i16));
-        let new_row = ((self.row as i16) + real_row_delta) as u16;
-        let new_col = ((self.col as i16) + real_col_delta) as u16;
-        self.go_to(new_row, new_col, data, config);
-    }
-
-    pub fn go_to(&mut self, row: u16, col: u16, data: &Data, config: &Config) {
-        self.row = row;
-        self.col = col;
-        self.fix_cursor_bounds(data, config);
-        let term_col = self.adjust_col(self.col, config);
-        let term_row = self.adjust_row(self.row, config);
-        write!(self.stdout,
-               "{}",
-               cursor::Goto(term_col, term_row)
-              ).unwrap();
+    }
+
+    /* Moving up or down keeps the cursor on the same cell of the screen
+     * when it can, which is another col in rows with tabs or wide chars. */
+    pub fn move_cursor(&mut self, row_delta: isize, col_delta: isize, buffers: &[Buffer], config: &Config) {
+        let (row, col) = self.cursor();
+        let new_row = row.saturating_add_signed(row_delta);
+        let mut new_col = col.saturating_add_signed(col_delta);
+        let buffer = &buffers[self.buffer()];
+        if new_row != row && new_row < buffer.data.len() {
+            let (cell, _) = Term::screen_col(self.win(), buffer, config);
+            let tab_width = buffer.tabs(config).width as usize;
+            let text = buffer.data.get_row(new_row);
+            new_col = lib::col_at_cell(&text, cell, tab_width);
+        }
+        self.go_to(new_row, new_col);
+    }
+
+    // The window scrolls to the cursor when the screen is presented
+    pub fn go_to(&mut self, row: usize, col: usize) {
+        self.win_mut().row = row;
+        self.win_mut().col = col;
+    }
+
+    /* Scrolls the active window to its cursor and sends what changed on the
+     * grid to the terminal, in a single write. */
+    pub fn present(&mut self, buffers: &[Buffer], config: &Config) {
+        let (term_col, term_row) = match self.cursor_at.take() {
+            Some(cursor) => cursor,
+            None         => {
+                self.fix_cursor_bounds(buffers, config);
+                let (cell, _) = Term::screen_col(self.win(), &buffers[self.buffer()], config);
+                (self.adjust_col(cell, config), self.adjust_row(self.win().row))
+            }
+        };
+        let frame = self.grid.render(term_col, term_row);
+        self.stdout.write_all(frame.as_bytes()).unwrap();
         self.stdout.flush().unwrap();
     }
 
-    pub fn rewind(&mut self, data: &Data, config: &Config) {
-        self.go_to(self.row, self.col, data, config);
+    // Redraws the buffer of the active window, wherever it is shown
+    pub fn draw_text(&mut self, buffers: &[Buffer], config: &Config) {
+        let buffer = &buffers[self.buffer()];
+        for index in self.sharing_windows() {
+            if index != self.active {
+                self.windows[index].clamp(&buffer.data);
+            }
+            self.draw_window(index, buffers, config);
+        }
     }
 
-    pub fn draw_text(&mut self, data: &Data, config: &Config) {
-        let non_empty_rows = std::cmp::min(self.vert_offset + config.height()
-                                          ,data.len() as u16);
-        for row in self.vert_offset .. non_empty_rows {
-                self.draw_row(row, data, config);
+    // Redraws every window, with their status lines and the separators
+    pub fn draw_all(&mut self, buffers: &[Buffer], config: &Config) {
+        for index in 0 .. self.windows.len() {
+            let buffer = &buffers[self.windows[index].buffer];
+            self.windows[index].clamp(&buffer.data);
+            self.draw_window(index, buffers, config);
         }
-        write!(self.stdout, "{}", color::Fg(color::Reset)).unwrap();
-        for row in data.len() as u16 .. self.vert_offset + config.height() {
-            write!(self.stdout,
-                   "{}{}~",
-                   cursor::Goto(1, self.adjust_row(row, config)),
-                   clear::UntilNewline
-                  ).unwrap();
+        self.grid.set_style(config.theme().style("default"));
+        for (col, top, height) in self.separators.clone() {
+            for row in top .. top + height {
+                self.grid.goto(col, row);
+                self.grid.write("│");
+            }
         }
-        self.rewind(data, config);
+        self.draw_status_line(buffers, config);
     }
 
-    pub fn set_message(&mut self, msg: &str, data: &Data, config: &Config) {
-        write!(self.stdout,
-               "{}{}{}{}",
-               cursor::Goto(1, config.height() + 2),
-               clear::UntilNewline,
-               color::Fg(color::Reset),
-               msg,
-              ).unwrap();
-        self.rewind(data, config);
+    /* Draws a list over the text, with the selected item inverted. The list
+     * scrolls so that the selected item is always visible. */
+    pub fn draw_picker(&mut self, title: &str, items: &[String], selected: usize, config: &Config) {
+        let visible = std::cmp::max(config.height() as usize, 2) - 1;
+        let first = if selected < visible { 0 } else { selected + 1 - visible };
+        let default = config.theme().style("default");
+        self.grid.set_style(default);
+        self.grid.goto(1, config.min_row());
+        self.grid.clear_until_newline();
+        self.grid.set_style(config.theme().style("title"));
+        self.grid.write(title);
+        for line in 0 .. visible {
+            self.grid.set_style(default);
+            self.grid.goto(1, config.min_row() + 1 + line as u16);
+            self.grid.clear_until_newline();
+            if let Some(item) = items.get(first + line) {
+                self.grid.set_invert(first + line == selected);
+                self.grid.write(&format!("  {}", item));
+            }
+        }
+        self.cursor_at = Some((1, config.min_row() + (selected - first) as u16 + 1));
     }
 
-    pub fn draw_status_line(&mut self, data: &Data, config: &Config) {
-        let displayed_name = if config.file_name().is_empty() {
-            "[No Name]".to_string()
-        } else {
-            config.file_name().clone()
-        };
+    pub fn set_message(&mut self, msg: &str, config: &Config) {
+        self.grid.set_style(config.theme().style("default"));
+        self.grid.goto(1, config.height() + 2);
+        self.grid.clear_until_newline();
+        self.grid.write(msg);
+    }
 
-        let pos_info = "L: ".to_string()       +
-                       &self.row.to_string()   +
-                       "/"                     +
-                       &data.len().to_string() +
-                       "  |  C: "              +
-                       &self.col.to_string()   +
-                       "/"                     +
-                       &data.row_length(self.row).to_string();
-
-        if config.width() <= (displayed_name.len() + pos_info.len()) as u16 {
-            panic!("terminal too thin!");
+    // Draws the status line of every window, the active one highlighted
+    pub fn draw_status_line(&mut self, buffers: &[Buffer], config: &Config) {
+        for index in 0 .. self.windows.len() {
+            self.draw_window_status(index, &buffers[self.windows[index].buffer], config);
         }
+    }
 
-        let rem_space =
-          config.width() as usize - displayed_name.len() - pos_info.len();
-        let middle: String =
-            std::iter::repeat(" ").take(rem_space as usize).collect();
+    fn draw_window_status(&mut self, index: usize, buffer: &Buffer, config: &Config) {
+        let window = self.windows[index];
+        let mut displayed_name = match self.mode {
+            Some(mode) if index == self.active => format!("-- {} -- {}", mode, buffer.display_name()),
+            _                                  => buffer.display_name(),
+        };
+        if buffer.is_modified() {
+            displayed_name.push_str(" [+]");
+        }
 
-        let text = displayed_name + &middle + &pos_info;
-        write!(self.stdout,
-               "{}{}{}{}{}{}{}",
-               cursor::Goto(1, config.height() + 1),
-               clear::UntilNewline,
-               color::Bg(color::White),
-               color::Fg(color::Black),
-               text,
-               color::Bg(color::Reset),
-               color::Fg(color::Reset),
-              ).unwrap();
-        self.rewind(data, config);
+        let pos_info = "L: ".to_string()              +
+                       &window.row.to_string()        +
+                       "/"                            +
+                       &buffer.data.len().to_string() +
+                       "  |  C: "                     +
+                       &window.col.to_string()        +
+                       "/"                            +
+                       &buffer.data.row_length(window.row).to_string();
+
+        // in narrow windows the name is cut, and then the position too
+        let width = window.width as usize;
+        let name_space = width.saturating_sub(pos_info.len() + 1);
+        let mut text = lib::truncate_to_width(&displayed_name, name_space);
+        text.push_str(&" ".repeat(width.saturating_sub(lib::display_width(&text) + pos_info.len())));
+        text.push_str(&pos_info);
+        let text = lib::truncate_to_width(&text, width);
+
+        let style = if index == self.active { "status" } else { "status_inactive" };
+        self.grid.goto(window.left, window.top + window.height);
+        self.grid.set_style(config.theme().style(style));
+        self.grid.write(&text);
     }
 
-    /* Turn the terminal back from Raw mode and ends the program */
-    pub fn die(&mut self, config: &Config) {
-        let goodbye_message: &str = "Good Bye!";
-        let first_line_col: usize =
-              (config.width() as usize - goodbye_message.len()) / 2;
-        write!(self.stdout,
-               "{}{}{}{}{}",
-               cursor::Show,
-               clear::All,
-               cursor::Goto(first_line_col as u16, 1),
-               color::Fg(color::Reset),
-               goodbye_message
-              ).unwrap();
+    // Sends text to the clipboard of the terminal, which may ignore it
+    pub fn copy_to_terminal(&mut self, text: &str) {
+        let _ = write!(self.stdout, "{}", clipboard::osc52(text));
+        let _ = self.stdout.flush();
+    }
 
-        write!(self.stdout, "{}", cursor::Goto(1, 2)).unwrap();
-        self.stdout.flush().unwrap();
-        self.stdout.suspend_raw_mode().unwrap();
+    /* Puts the terminal back as it was before start. Errors are ignored, as
+     * this is also done when things already went wrong. */
+    pub fn restore(&mut self) {
+        let _ = write!(self.stdout, "{}{}{}{}",
+                       BRACKETED_PASTE_OFF, style::Reset, cursor::Show, screen::ToMainScreen);
+        let _ = self.stdout.flush();
+        let _ = self.stdout.suspend_raw_mode();
+    }
+
+    pub fn die(&mut self) -> ! {
+        self.restore();
+        println!("Good Bye!");
         process::exit(0);
     }
 }
+
+// Also when the editor stops because of a panic
+impl Drop for Term {
+    fn drop(&mut self) {
+        self.restore();
+    }
+}
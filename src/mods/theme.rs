@@ -0,0 +1,155 @@
+use std::collections::HashMap;
+
+use super::grid::{ Color, Style };
+
+// The styles of a theme: for the tokens of the text, and then for the rest of the screen
+pub static STYLE_NAMES: [&str; 13] =
+    [ "default"
+    , "keyword"
+    , "type_name"
+    , "function"
+    , "number"
+    , "comment"
+    , "string"
+    , "operator"
+    , "line_number"
+    , "tilde"
+    , "title"
+    , "status"
+    , "status_inactive"
+    ];
+
+pub static THEME_NAMES: [&str; 2] = ["dark", "light"];
+
+// Names of the first 16 colors, in the order of their codes
+static COLOR_NAMES: [&str; 16] =
+    [ "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"
+    , "gray", "bright_red", "bright_green", "bright_yellow"
+    , "bright_blue", "bright_magenta", "bright_cyan", "bright_white"
+    ];
+
+/* The styles the screen is drawn with. Styles without a color of their own
+ * take the one of the default style, so that a theme with a background
+ * paints the whole screen. */
+#[derive(Clone)]
+pub struct Theme {
+    name   : &'static str,
+    styles : HashMap<&'static str, Style>,
+}
+
+impl Theme {
+    // One of the bundled themes, given its name in THEME_NAMES
+    pub fn named(name: &str) -> Option<Theme> {
+        match name {
+            "dark"  => Some(Theme::dark()),
+            "light" => Some(Theme::light()),
+            _       => None,
+        }
+    }
+
+    fn from_styles(name: &'static str, styles: &[(&'static str, &str)]) -> Theme {
+        let styles = styles.iter()
+                           .map(|&(kind, style)| (kind, parse_style(style).unwrap()))
+                           .collect();
+        Theme { name, styles }
+    }
+
+    fn dark() -> Theme {
+        Theme::from_styles("dark", &[
+            ("default",         "#dcdfe4"),
+            ("keyword",         "bold #98c379"),
+            ("type_name",       "#61afef"),
+            ("function",        "#e5c07b"),
+            ("number",          "#e06c75"),
+            ("comment",         "italic #7f848e"),
+            ("string",          "#c678dd"),
+            ("operator",        "#56b6c2"),
+            ("line_number",     "#d7af5f"),
+            ("tilde",           "#5c6370"),
+            ("title",           "bold #e5c07b"),
+            ("status",          "#282c34 on #abb2bf"),
+            ("status_inactive", "#abb2bf on #3e4451"),
+        ])
+    }
+
+    fn light() -> Theme {
+        Theme::from_styles("light", &[
+            ("default",         "#383a42 on #fafafa"),
+            ("keyword",         "bold #50a14f"),
+            ("type_name",       "#4078f2"),
+            ("function",        "#c18401"),
+            ("number",          "#986801"),
+            ("comment",         "italic #a0a1a7"),
+            ("string",          "#a626a4"),
+            ("operator",        "#0184bc"),
+            ("line_number",     "#9d9d9f"),
+            ("tilde",           "#a0a1a7"),
+            ("title",           "bold #c18401"),
+            ("status",          "#fafafa on #383a42"),
+            ("status_inactive", "#383a42 on #dbdbdc"),
+        ])
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    // Replaces one of the styles, given by its name in STYLE_NAMES
+    pub fn set(&mut self, name: &'static str, style: Style) {
+        self.styles.insert(name, style);
+    }
+
+    pub fn style(&self, name: &str) -> Style {
+        self.fill(self.styles.get(name).copied().unwrap_or_default())
+    }
+
+    // Gives the colors of the default style to a style that has none
+    pub fn fill(&self, style: Style) -> Style {
+        let default = self.styles.get("default").copied().unwrap_or_default();
+        Style {
+            fg: if style.fg == Color::Reset { default.fg } else { style.fg },
+            bg: if style.bg == Color::Reset { default.bg } else { style.bg },
+            ..style
+        }
+    }
+}
+
+/* Reads a color given by its name (see COLOR_NAMES), by its code among the
+ * 256 colors of the terminal, or as "#rrggbb". */
+pub fn parse_color(text: &str) -> Option<Color> {
+    if let Some(hex) = text.strip_prefix('#') {
+        let channel = |index: usize| hex.get(index .. index + 2)
+                                        .and_then(|digits| u8::from_str_radix(digits, 16).ok());
+        return match hex.len() {
+            6 => Some(Color::Rgb(channel(0)?, channel(2)?, channel(4)?)),
+            _ => None,
+        };
+    }
+    if text == "default" {
+        return Some(Color::Reset);
+    }
+    COLOR_NAMES.iter()
+               .position(|&name| name == text)
+               .map(|code| code as u8)
+               .or_else(|| text.parse().ok())
+               .map(Color::Ansi)
+}
+
+/* Reads a style like "bold #ff8800 on 236": attributes, then the color of
+ * the text, and the color of the background after "on". Any of them can be
+ * left out. */
+pub fn parse_style(text: &str) -> Option<Style> {
+    let mut style = Style::default();
+    let mut words = text.split_whitespace();
+    while let Some(word) = words.next() {
+        match word {
+            "bold"      => style.bold = true,
+            "italic"    => style.italic = true,
+            "underline" => style.underline = true,
+            "reverse"   => style.invert = true,
+            "on"        => style.bg = parse_color(words.next()?)?,
+            _           => style.fg = parse_color(word)?,
+        }
+    }
+    Some(style)
+}
@@ -0,0 +1,207 @@
+use super::data::Data;
+
+// Cursor position and scroll offsets, saved to come back to them later
+#[derive(Clone, Copy, Default)]
+pub struct View {
+    pub row         : usize,
+    pub col         : usize,
+    pub vert_offset : usize,
+    pub hor_offset  : usize,
+}
+
+/* A rectangle of the screen showing one of the buffers. Each window has its
+ * own cursor and scroll offsets, so the same buffer can be shown at two
+ * different places. The status line of the window is drawn below its text. */
+#[derive(Clone, Copy, Default)]
+pub struct Window {
+    pub buffer      : usize,
+    pub row         : usize,
+    pub col         : usize,
+    pub vert_offset : usize,
+    pub hor_offset  : usize,
+    pub top         : u16,
+    pub left        : u16,
+    pub height      : u16,
+    pub width       : u16,
+}
+
+impl Window {
+    pub fn new(buffer: usize) -> Window {
+        Window { buffer, ..Default::default() }
+    }
+
+    pub fn view(&self) -> View {
+        View {
+            row: self.row,
+            col: self.col,
+            vert_offset: self.vert_offset,
+            hor_offset: self.hor_offset,
+        }
+    }
+
+    /* Goes back to a saved view, kept inside the text, which may have
+     * shrunk since it was saved. */
+    pub fn set_view(&mut self, view: View, data: &Data) {
+        self.row = view.row;
+        self.col = view.col;
+        self.vert_offset = view.vert_offset;
+        self.hor_offset = view.hor_offset;
+        self.clamp(data);
+    }
+
+    /* Keeps the cursor inside the text, which may have shrunk through another
+     * window. Tells if it had to move. */
+    pub fn clamp(&mut self, data: &Data) -> bool {
+        let mut moved = false;
+        if self.row >= data.len() {
+            self.row = data.len() - 1;
+            moved = true;
+        }
+        if self.col > data.row_length(self.row) {
+            self.col = data.row_length(self.row);
+            moved = true;
+        }
+        if self.vert_offset > self.row {
+            self.vert_offset = self.row;
+            moved = true;
+        }
+        moved
+    }
+}
+
+// Smallest window that can still show a few rows and its status line
+pub const MIN_HEIGHT: u16 = 3;
+pub const MIN_WIDTH: u16 = 20;
+
+/* How the screen is divided between the windows. A vertical split puts its
+ * parts side by side, separated by a column, and an horizontal one puts
+ * them one above the other. */
+pub enum Layout {
+    Window(usize),
+    Split { vertical: bool, first: Box<Layout>, second: Box<Layout> },
+}
+
+impl Layout {
+    /* Gives each window its part of the area starting at (top, left), which
+     * includes the status lines. Returns the columns used as separators, as
+     * (col, top, height) triples. */
+    pub fn arrange(&self, windows: &mut [Window], top: u16, left: u16, height: u16, width: u16)
+                  -> Vec<(u16, u16, u16)> {
+        match self {
+            Layout::Window(index) => {
+                let window = &mut windows[*index];
+                window.top = top;
+                window.left = left;
+                // a terminal too small for all the windows leaves them at least a row
+                window.height = std::cmp::max(height.saturating_sub(1), 1);
+                window.width = width;
+                Vec::new()
+            }
+            Layout::Split { vertical: false, first, second } => {
+                let first_height = height / 2;
+                let mut separators = first.arrange(windows, top, left, first_height, width);
+                separators.extend(second.arrange(windows,
+                                                 top + first_height,
+                                                 left,
+                                                 height - first_height,
+                                                 width));
+                separators
+            }
+            Layout::Split { vertical: true, first, second } => {
+                let first_width = width.saturating_sub(1) / 2;
+                let mut separators = first.arrange(windows, top, left, height, first_width);
+                separators.push((left + first_width, top, height));
+                separators.extend(second.arrange(windows,
+                                                 top,
+                                                 left + first_width + 1,
+                                                 height,
+                                                 width.saturating_sub(first_width + 1)));
+                separators
+            }
+        }
+    }
+
+    // Replaces the given window by a split between it and new_window
+    pub fn split(&mut self, window: usize, new_window: usize, vertical: bool) {
+        match self {
+            Layout::Window(index) if *index == window => {
+                *self = Layout::Split {
+                    vertical,
+                    first: Box::new(Layout::Window(window)),
+                    second: Box::new(Layout::Window(new_window)),
+                };
+            }
+            Layout::Window(_) => (),
+            Layout::Split { first, second, .. } => {
+                first.split(window, new_window, vertical);
+                second.split(window, new_window, vertical);
+            }
+        }
+    }
+
+    /* Takes the window out of the layout, giving its space to its sibling.
+     * Windows after it are renumbered, as they are removed from a Vec. */
+    pub fn remove(&mut self, window: usize) {
+        self.take_out(window);
+        self.renumber(window);
+    }
+
+    fn take_out(&mut self, window: usize) {
+        if let Layout::Split { first, second, .. } = self {
+            let is_window = |layout: &Layout| matches!(layout, Layout::Window(index) if *index == window);
+            if is_window(first) {
+                *self = std::mem::replace(&mut **second, Layout::Window(0));
+            } else if is_window(second) {
+                *self = std::mem::replace(&mut **first, Layout::Window(0));
+            } else {
+                first.take_out(window);
+                second.take_out(window);
+            }
+        }
+    }
+
+    fn renumber(&mut self, removed: usize) {
+        match self {
+            Layout::Window(index) if *index > removed => *index -= 1,
+            Layout::Window(_) => (),
+            Layout::Split { first, second, .. } => {
+                first.renumber(removed);
+                second.renumber(removed);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Window;
+    use crate::mods::data::Data;
+
+    #[test]
+    fn a_view_is_clamped_to_the_text_that_shrank_since() {
+        let mut data = Data::from_text(&"some text\n".repeat(200));
+        let mut window = Window::new(0);
+        window.row = 150;
+        window.col = 5;
+        window.vert_offset = 140;
+        let view = window.view();
+        // another window cuts everything, and this one switches back to it
+        let (last, end) = (data.len() - 1, data.row_length(data.len() - 1));
+        data.remove_text(0, 0, last, end);
+        window.set_view(view, &data);
+        assert_eq!((window.row, window.col, window.vert_offset), (0, 0, 0));
+    }
+
+    #[test]
+    fn a_view_inside_the_text_is_kept() {
+        let data = Data::from_text("one\ntwo\nthree");
+        let mut window = Window::new(0);
+        window.row = 2;
+        window.col = 3;
+        window.vert_offset = 1;
+        let view = window.view();
+        let mut other = Window::new(0);
+        other.set_view(view, &data);
+        assert_eq!((other.row, other.col, other.vert_offset), (2, 3, 1));
+    }
+}